@@ -0,0 +1,201 @@
+// Date arithmetic for recurring sessions. Recurrences are expanded in the
+// organizer's local time using a fixed UTC offset, so a series keeps its
+// wall-clock time only while that offset holds (DST changes are not applied).
+
+pub const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// Upper bound on candidate dates examined per expansion, so a sparse rule
+// cannot make a call loop for long.
+const MAX_STEPS: usize = 10_000;
+
+pub const WEEKDAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+// Days since 1970-01-01 for a proleptic Gregorian date.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// Inverse of `days_from_civil`.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    (days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)) as u32
+}
+
+// 0 = Monday .. 6 = Sunday. 1970-01-01 was a Thursday.
+pub fn weekday(days: i64) -> u8 {
+    (days + 3).rem_euclid(7) as u8
+}
+
+// Splits a UTC timestamp into the local day number and seconds into that day.
+pub fn local_day_and_time(utc_nanos: u64, offset_minutes: i32) -> (i64, i64) {
+    let local_seconds = (utc_nanos / NANOS_PER_SECOND) as i64 + offset_minutes as i64 * 60;
+    (local_seconds.div_euclid(SECONDS_PER_DAY), local_seconds.rem_euclid(SECONDS_PER_DAY))
+}
+
+fn to_utc_nanos(day: i64, seconds: i64, offset_minutes: i32) -> Option<u64> {
+    let utc_seconds = day * SECONDS_PER_DAY + seconds - offset_minutes as i64 * 60;
+    u64::try_from(utc_seconds).ok().map(|s| s * NANOS_PER_SECOND)
+}
+
+// Start times of a weekly rule in `[from, to]`. The series repeats every
+// `interval` weeks, counted from the week of `first_start`, on the given weekdays.
+pub fn weekly_occurrences(
+    first_start: u64,
+    offset_minutes: i32,
+    interval: u32,
+    weekdays: &[u8],
+    from: u64,
+    to: u64,
+) -> Vec<u64> {
+    let (first_day, time_of_day) = local_day_and_time(first_start, offset_minutes);
+    let first_week = first_day - weekday(first_day) as i64;
+    let mut occurrences = vec![];
+
+    for step in 0..MAX_STEPS {
+        let week_start = first_week + step as i64 * 7 * interval as i64;
+        for &day_of_week in weekdays {
+            let day = week_start + day_of_week as i64;
+            if day < first_day {
+                continue;
+            }
+            match to_utc_nanos(day, time_of_day, offset_minutes) {
+                Some(start) if start > to => return occurrences,
+                Some(start) if start >= from => occurrences.push(start),
+                _ => {}
+            }
+        }
+    }
+    occurrences
+}
+
+// Start times of a monthly rule in `[from, to]`: the same day of the month as
+// `first_start`, every `interval` months. Months without that day are skipped.
+pub fn monthly_occurrences(first_start: u64, offset_minutes: i32, interval: u32, from: u64, to: u64) -> Vec<u64> {
+    let (first_day, time_of_day) = local_day_and_time(first_start, offset_minutes);
+    let (year, month, day_of_month) = civil_from_days(first_day);
+    let mut occurrences = vec![];
+
+    for step in 0..MAX_STEPS {
+        let months = (month - 1) as i64 + step as i64 * interval as i64;
+        let (year, month) = (year + months.div_euclid(12), months.rem_euclid(12) as u32 + 1);
+        if day_of_month > days_in_month(year, month) {
+            continue;
+        }
+        let day = days_from_civil(year, month, day_of_month);
+        match to_utc_nanos(day, time_of_day, offset_minutes) {
+            Some(start) if start > to => break,
+            Some(start) if start >= from => occurrences.push(start),
+            _ => {}
+        }
+    }
+    occurrences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // UTC timestamp in nanoseconds.
+    fn utc(year: i64, month: u32, day: u32, hour: i64, minute: i64) -> u64 {
+        let seconds = days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * 3600 + minute * 60;
+        seconds as u64 * NANOS_PER_SECOND
+    }
+
+    #[test]
+    fn civil_dates_round_trip() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        for days in (-800_000..800_000).step_by(97) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn weekdays_count_from_monday() {
+        assert_eq!(weekday(0), 3);
+        assert_eq!(weekday(days_from_civil(2024, 1, 1)), 0);
+        assert_eq!(weekday(days_from_civil(2024, 3, 3)), 6);
+    }
+
+    #[test]
+    fn weekly_rule_repeats_on_the_chosen_days() {
+        let first = utc(2024, 1, 1, 9, 0);
+        let occurrences = weekly_occurrences(first, 0, 1, &[0, 2], first, utc(2024, 1, 14, 0, 0));
+        assert_eq!(
+            occurrences,
+            vec![utc(2024, 1, 1, 9, 0), utc(2024, 1, 3, 9, 0), utc(2024, 1, 8, 9, 0), utc(2024, 1, 10, 9, 0)]
+        );
+    }
+
+    #[test]
+    fn weekly_rule_skips_weeks_by_interval() {
+        let first = utc(2024, 1, 1, 9, 0);
+        let occurrences = weekly_occurrences(first, 0, 2, &[0, 2], first, utc(2024, 1, 20, 0, 0));
+        assert_eq!(
+            occurrences,
+            vec![utc(2024, 1, 1, 9, 0), utc(2024, 1, 3, 9, 0), utc(2024, 1, 15, 9, 0), utc(2024, 1, 17, 9, 0)]
+        );
+    }
+
+    #[test]
+    fn weekly_rule_starts_at_the_first_session() {
+        let first = utc(2024, 1, 3, 9, 0);
+        let occurrences = weekly_occurrences(first, 0, 1, &[0, 2], 0, utc(2024, 1, 10, 9, 0));
+        assert_eq!(occurrences, vec![utc(2024, 1, 3, 9, 0), utc(2024, 1, 8, 9, 0), utc(2024, 1, 10, 9, 0)]);
+    }
+
+    #[test]
+    fn weekly_rule_uses_the_local_weekday() {
+        // 02:00 UTC on Monday is 21:00 on Sunday at UTC-5.
+        let first = utc(2024, 1, 1, 2, 0);
+        let occurrences = weekly_occurrences(first, -300, 1, &[6], first, utc(2024, 1, 9, 0, 0));
+        assert_eq!(occurrences, vec![utc(2024, 1, 1, 2, 0), utc(2024, 1, 8, 2, 0)]);
+    }
+
+    #[test]
+    fn monthly_rule_skips_months_without_the_day() {
+        let first = utc(2024, 1, 31, 18, 30);
+        let occurrences = monthly_occurrences(first, 0, 1, first, utc(2024, 12, 31, 23, 59));
+        let days: Vec<(u32, u32)> = occurrences
+            .iter()
+            .map(|start| {
+                let (_, month, day) = civil_from_days((*start / NANOS_PER_SECOND) as i64 / SECONDS_PER_DAY);
+                (month, day)
+            })
+            .collect();
+        assert_eq!(days, vec![(1, 31), (3, 31), (5, 31), (7, 31), (8, 31), (10, 31), (12, 31)]);
+    }
+
+    #[test]
+    fn monthly_rule_honours_interval_and_window() {
+        let first = utc(2024, 1, 15, 10, 0);
+        let occurrences = monthly_occurrences(first, 0, 3, utc(2024, 2, 1, 0, 0), utc(2025, 1, 15, 10, 0));
+        assert_eq!(
+            occurrences,
+            vec![utc(2024, 4, 15, 10, 0), utc(2024, 7, 15, 10, 0), utc(2024, 10, 15, 10, 0), utc(2025, 1, 15, 10, 0)]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// Binary Merkle tree over issued certificates. Leaves and inner nodes use
+// distinct prefixes so a leaf can never be passed off as an inner node.
+//
+// Leaves are only ever appended, so the tree is stored node by node and an
+// append only rewrites the path from the new leaf to the root. Node (level,
+// position) covers leaves [position << level, (position + 1) << level); an odd
+// node out at the end of a level is carried up unchanged.
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+pub fn empty_root() -> Hash {
+    Sha256::digest([]).into()
+}
+
+// Number of nodes on `level` of a tree with `leaf_count` leaves.
+fn level_len(leaf_count: u64, level: u8) -> u64 {
+    if leaf_count == 0 {
+        0
+    } else {
+        ((leaf_count - 1) >> level) + 1
+    }
+}
+
+// Level of the root node in a non-empty tree.
+pub fn root_level(leaf_count: u64) -> u8 {
+    let mut level = 0;
+    while level_len(leaf_count, level) > 1 {
+        level += 1;
+    }
+    level
+}
+
+// Appends a leaf at `index` to a tree holding leaves 0..index, reading stored
+// nodes through `node`. Returns the nodes to store as (level, position, hash),
+// from the leaf up to the new root.
+pub fn append(leaf: Hash, index: u64, node: impl Fn(u8, u64) -> Hash) -> Vec<(u8, u64, Hash)> {
+    let leaf_count = index + 1;
+    let (mut level, mut position, mut hash) = (0, index, leaf);
+    let mut updates = vec![(level, position, hash)];
+    while level_len(leaf_count, level) > 1 {
+        // The new leaf is always the last one, so any sibling is on the left.
+        if position % 2 == 1 {
+            hash = node_hash(&node(level, position - 1), &hash);
+        }
+        level += 1;
+        position /= 2;
+        updates.push((level, position, hash));
+    }
+    updates
+}
+
+// Sibling hashes from the leaf at `index` up to the root, with whether each
+// sibling sits on the left.
+pub fn proof(index: u64, leaf_count: u64, node: impl Fn(u8, u64) -> Hash) -> Vec<(Hash, bool)> {
+    let mut steps = vec![];
+    let (mut level, mut position) = (0, index);
+    while level_len(leaf_count, level) > 1 {
+        let sibling = position ^ 1;
+        if sibling < level_len(leaf_count, level) {
+            steps.push((node(level, sibling), sibling < position));
+        }
+        level += 1;
+        position /= 2;
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn build(leaf_count: u64) -> (Vec<Hash>, HashMap<(u8, u64), Hash>) {
+        let leaves: Vec<Hash> = (0..leaf_count).map(|i| leaf_hash(&i.to_be_bytes())).collect();
+        let mut tree = HashMap::new();
+        for (index, leaf) in leaves.iter().enumerate() {
+            let updates = append(*leaf, index as u64, |level, position| tree[&(level, position)]);
+            tree.extend(updates.into_iter().map(|(level, position, hash)| ((level, position), hash)));
+        }
+        (leaves, tree)
+    }
+
+    // Root computed from scratch, one level at a time.
+    fn full_root(leaves: &[Hash]) -> Hash {
+        let mut level = leaves.to_vec();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        level[0]
+    }
+
+    fn fold(leaf: Hash, steps: &[(Hash, bool)]) -> Hash {
+        steps.iter().fold(leaf, |hash, (sibling, sibling_is_left)| {
+            if *sibling_is_left {
+                node_hash(sibling, &hash)
+            } else {
+                node_hash(&hash, sibling)
+            }
+        })
+    }
+
+    #[test]
+    fn stored_root_matches_full_rebuild() {
+        for leaf_count in 1..=33 {
+            let (leaves, tree) = build(leaf_count);
+            assert_eq!(tree[&(root_level(leaf_count), 0)], full_root(&leaves), "{} leaves", leaf_count);
+        }
+    }
+
+    #[test]
+    fn every_proof_folds_to_the_root() {
+        for leaf_count in 1..=33 {
+            let (leaves, tree) = build(leaf_count);
+            let root = tree[&(root_level(leaf_count), 0)];
+            for (index, leaf) in leaves.iter().enumerate() {
+                let steps = proof(index as u64, leaf_count, |level, position| tree[&(level, position)]);
+                assert_eq!(fold(*leaf, &steps), root, "leaf {} of {}", index, leaf_count);
+            }
+        }
+    }
+
+    #[test]
+    fn proof_rejects_a_different_leaf() {
+        let (_, tree) = build(5);
+        let root = tree[&(root_level(5), 0)];
+        let steps = proof(2, 5, |level, position| tree[&(level, position)]);
+        assert_ne!(fold(leaf_hash(b"forged"), &steps), root);
+    }
+
+    #[test]
+    fn single_leaf_is_its_own_root() {
+        let (leaves, tree) = build(1);
+        assert_eq!(root_level(1), 0);
+        assert_eq!(tree[&(0, 0)], leaves[0]);
+        assert!(proof(0, 1, |level, position| tree[&(level, position)]).is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+// Minimal iCalendar (RFC 5545) writer for calendar feeds. All times are
+// written in UTC, so no VTIMEZONE components are needed.
+use crate::calendar::{civil_from_days, NANOS_PER_SECOND};
+
+// Content lines longer than this many octets must be folded.
+const MAX_LINE_OCTETS: usize = 75;
+
+pub struct Event {
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub start: u64,
+    // None for instants such as deadlines, which are written without a DTEND.
+    pub end: Option<u64>,
+    pub cancelled: bool,
+    pub last_modified: u64,
+}
+
+pub fn calendar(name: &str, events: &[Event], now: u64) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Cogni//Study Calendar//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", format_utc(now)));
+        lines.push(format!("DTSTART:{}", format_utc(event.start)));
+        if let Some(end) = event.end {
+            lines.push(format!("DTEND:{}", format_utc(end)));
+        }
+        lines.push(format!("LAST-MODIFIED:{}", format_utc(event.last_modified)));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+        }
+        lines.push(format!("STATUS:{}", if event.cancelled { "CANCELLED" } else { "CONFIRMED" }));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold_line(line)).collect::<Vec<_>>().join("")
+}
+
+// Formats a nanosecond timestamp as a UTC date-time, e.g. 20240131T093000Z.
+pub fn format_utc(nanos: u64) -> String {
+    let seconds = (nanos / NANOS_PER_SECOND) as i64;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let time = seconds.rem_euclid(86_400);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// Splits a content line into CRLF-terminated chunks of at most 75 octets,
+// continuation lines starting with a space, without breaking UTF-8 characters.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_reserved_characters() {
+        assert_eq!(escape_text("a,b;c\\d"), "a\\,b\\;c\\\\d");
+        assert_eq!(escape_text("one\r\ntwo\nthree"), "one\\ntwo\\nthree");
+    }
+
+    #[test]
+    fn short_lines_are_only_terminated() {
+        assert_eq!(fold_line("SUMMARY:Algebra"), "SUMMARY:Algebra\r\n");
+        let exact = "X".repeat(MAX_LINE_OCTETS);
+        assert_eq!(fold_line(&exact), format!("{}\r\n", exact));
+    }
+
+    #[test]
+    fn long_lines_fold_at_75_octets() {
+        let line = "D".repeat(200);
+        let folded = fold_line(&line);
+        let chunks: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert!(chunks.iter().all(|chunk| chunk.len() <= MAX_LINE_OCTETS));
+        assert!(chunks[1..].iter().all(|chunk| chunk.starts_with(' ')));
+        let unfolded: String = chunks.iter().enumerate().map(|(i, c)| if i == 0 { *c } else { &c[1..] }).collect();
+        assert_eq!(unfolded, line);
+    }
+
+    #[test]
+    fn folding_keeps_multibyte_characters_whole() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold_line(&line);
+        for chunk in folded.trim_end_matches("\r\n").split("\r\n") {
+            assert!(chunk.len() <= MAX_LINE_OCTETS);
+        }
+        let unfolded = folded.replace("\r\n ", "");
+        assert_eq!(unfolded.trim_end_matches("\r\n"), line);
+    }
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(format_utc(0), "19700101T000000Z");
+        assert_eq!(format_utc(1_706_693_400 * NANOS_PER_SECOND), "20240131T093000Z");
+    }
+
+    #[test]
+    fn instants_are_written_without_an_end() {
+        let event = |end| Event {
+            uid: "task-1@cogni".to_string(),
+            summary: "Deadline: Essay".to_string(),
+            description: None,
+            start: 0,
+            end,
+            cancelled: false,
+            last_modified: 0,
+        };
+        assert!(!calendar("Study", &[event(None)], 0).contains("DTEND"));
+        assert!(calendar("Study", &[event(Some(NANOS_PER_SECOND))], 0).contains("DTEND:19700101T000001Z\r\n"));
+    }
+}
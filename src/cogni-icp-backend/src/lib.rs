@@ -1,17 +1,137 @@
+mod calendar;
+mod certification;
+mod ical;
 mod models;
 mod state;
 
-use models::user::{User, UserSettings};
+use models::user::{User, UserSettings, UserProfile};
 use models::tutor::Tutor;
 use state::{USERS, TUTORS, next_id};
-use std::collections::HashMap;
-use models::connections::{UserConnection, ConnectionRequest};
-use state::{CONNECTIONS, CONNECTION_REQUESTS};
+use state::{IndexTerm, USERS_BY_USERNAME, USERS_BY_INTEREST};
+use models::tutor::{TutorSession, TutorCourse, CourseModule, NewTutorCourse};
+use state::{TUTOR_SESSIONS, TUTORS_BY_USER, TUTOR_SESSIONS_BY_USER, TUTOR_COURSES, COURSES_BY_SESSION};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use models::connections::{UserConnection, ConnectionRequest, ConnectionPair, ConnectionSuggestion};
+use state::{CONNECTIONS, CONNECTION_REQUESTS, CONNECTION_PAIRS, CONNECTIONS_BY_USER, BLOCKS};
+use state::{REQUESTS_BY_SENDER, REQUESTS_BY_RECEIVER, PENDING_REQUESTS_BY_AGE};
 use candid::Principal;
-use models::study_group::{StudyGroup, GroupMembership};
-use state::{STUDY_GROUPS, GROUP_MEMBERSHIPS};
-use models::gamification::{Task, UserTaskCompletion};
-use state::{TASKS, USER_TASK_COMPLETIONS};
+use models::study_group::{StudyGroup, StudyGroupUpdate, GroupMembership, GroupJoinRequest, GroupInvite};
+use models::study_group::{StudyGroupStats, StudyGroupSummary, StudyGroupFilter};
+use state::{GROUP_STATS, GROUPS_BY_MEMBERS, GROUPS_BY_ACTIVITY, GROUPS_BY_LEVEL, GROUP_KEYWORDS};
+use models::study_group::activity::GroupMessage;
+use state::{GROUP_MESSAGES, GROUP_MESSAGE_REPLIES};
+use models::study_group::activity::{GroupActivity, GroupActivityStats, DailyCount};
+use state::{GROUP_ACTIVITIES, GROUP_ACTIVE_MEMBERS, GROUP_DAILY_MESSAGES};
+use models::study_group::activity::{StudyResource, NewStudyResource, StudyResourceUpdate, ResourceFile};
+use state::{STUDY_RESOURCES, RESOURCES_BY_GROUP, RESOURCE_FILES, RESOURCE_TAGS, GROUP_FILE_BYTES};
+use models::study_group::polls::{GroupPoll, PollOption, PollVote, NewPoll, PollOptionResult, PollResults};
+use state::{GROUP_POLLS, POLLS_BY_GROUP, POLL_OPTIONS, POLL_VOTES};
+use models::study_group::sessions::{StudySession, SessionParticipant, NewStudySession, UserSession};
+use models::study_group::sessions::{StudySessionUpdate, RecurrenceRule, SessionSeries};
+use models::study_group::sessions::{SessionAttendance, AttendanceStats, MemberAttendance, GroupAttendanceReport};
+use state::{STUDY_SESSIONS, SESSIONS_BY_GROUP, SESSION_PARTICIPANTS, SESSIONS_BY_USER, SESSION_ATTENDANCE};
+use state::{SESSION_SERIES, SERIES_BY_GROUP, SERIES_OCCURRENCES};
+use models::http::{HttpRequest, HttpResponse};
+use state::{CALENDAR_TOKENS, USER_CALENDAR_TOKENS};
+use state::{STUDY_GROUPS, GROUP_MEMBERSHIPS, MEMBERSHIPS_BY_USER, MEMBERSHIPS_BY_GROUP};
+use state::{GROUP_JOIN_REQUESTS, PENDING_JOIN_REQUESTS, GROUP_INVITES, INVITES_BY_GROUP};
+use models::gamification::{Task, UserTaskCompletion, Achievement, UserAchievement};
+use state::{ACHIEVEMENTS, USER_ACHIEVEMENTS, USER_ACHIEVEMENTS_BY_USER};
+use state::{TASKS, TASKS_BY_EXPIRY, USER_TASK_COMPLETIONS, TASK_COMPLETIONS_BY_USER};
+use models::learning_progress::{
+    LearningMetrics, LearningActivity, ComprehensionScore, EngagementMetrics,
+    TimeSpentBucket, LearningStreak, ComprehensionTrendPoint,
+    DifficultyAdjustment, DifficultyProfile, TopicDifficulty,
+};
+use state::{LEARNING_METRICS, DIFFICULTY_PROFILES, DIFFICULTY_ADJUSTMENTS};
+use models::learning_path::{LearningPath, LearningPathModule, PathRecommendation};
+use models::learning_progress::{LearningPathProgress, ModuleCompletion};
+use state::{LEARNING_PATHS, LEARNING_PATH_PROGRESS, PATH_MODULE_COMPLETIONS};
+use models::study_group::{Topic, TopicContents};
+use state::{TOPICS, TOPIC_GROUPS, TOPIC_PATHS, TOPIC_TUTORS, Memory};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use std::thread::LocalKey;
+use std::time::Duration;
+use models::certificates::{Certificate, CertificateProof, MerkleProofStep};
+use models::messages::{Conversation, DirectMessage, ConversationSummary};
+use state::{CONVERSATIONS, DIRECT_MESSAGES, CONVERSATIONS_BY_USER, CONVERSATION_PAIRS};
+use state::{CERTIFICATES, CERTIFICATE_LEAVES, CERTIFICATES_BY_USER, CERTIFICATE_POSITIONS, CERTIFICATE_TREE};
+use models::feed::{ActivityEvent, FeedPage, FollowStats};
+use state::{FOLLOWS, FOLLOWERS, ACTIVITY_EVENTS, EVENTS_BY_ACTOR, EVENTS_BY_GROUP};
+
+const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+const CONNECTION_REQUEST_TTL: u64 = 30 * NANOS_PER_DAY;
+const MAX_PAGE_SIZE: u64 = 100;
+// Most events examined per feed source (a user or a group) for one feed page.
+const FEED_SCAN_WINDOW: usize = 500;
+// Event types shown to followers: things users do in public.
+const FOLLOW_FEED_EVENT_TYPES: [&str; 3] = ["tutor_published", "path_completed", "achievement_unlocked"];
+const MAX_MESSAGE_LENGTH: usize = 2000;
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const NANOS_PER_HOUR: u64 = 3_600_000_000_000;
+const MAX_INVITE_TTL_HOURS: u64 = 30 * 24;
+const MAX_GROUP_NAME_LENGTH: usize = 100;
+const MAX_MESSAGE_ATTACHMENTS: usize = 5;
+// Uploads arrive in a single ingress message, which is capped at 2 MiB.
+const MAX_RESOURCE_FILE_BYTES: u64 = 1_500_000;
+const MAX_GROUP_FILE_BYTES: u64 = 50_000_000;
+const MAX_RESOURCE_TAGS: usize = 10;
+const MAX_POLL_OPTIONS: usize = 10;
+const NANOS_PER_MINUTE: u64 = 60_000_000_000;
+const MAX_SESSION_MINUTES: u32 = 24 * 60;
+// How far ahead occurrences of recurring sessions are generated.
+const SERIES_WINDOW: u64 = 8 * 7 * NANOS_PER_DAY;
+const MAX_RECURRENCE_INTERVAL: u32 = 12;
+// Past events older than this are left out of calendar feeds.
+const CALENDAR_HISTORY: u64 = 30 * NANOS_PER_DAY;
+const MAX_CALENDAR_EVENTS: usize = 500;
+// Self check-in opens this long before a session starts and closes when it ends.
+const CHECK_IN_OPENS_BEFORE: u64 = 15 * NANOS_PER_MINUTE;
+// Organizers can correct attendance for this long after a session ends.
+const ATTENDANCE_CORRECTION_WINDOW: u64 = 2 * NANOS_PER_DAY;
+const ATTENDANCE_POINTS: u32 = 10;
+const WALK_IN_POINTS: u32 = 5;
+// Longest window covered by group activity stats, in days.
+const ACTIVITY_STATS_DAYS: u64 = 30;
+
+const DIFFICULTY_LEVELS: [&str; 3] = ["beginner", "intermediate", "advanced"];
+// Number of recent scores considered when deciding on a difficulty change.
+const DIFFICULTY_WINDOW: usize = 5;
+const DIFFICULTY_MIN_SAMPLES: usize = 3;
+const DIFFICULTY_PROMOTE_THRESHOLD: f32 = 85.0;
+const DIFFICULTY_DEMOTE_THRESHOLD: f32 = 50.0;
+
+#[ic_cdk::init]
+fn init() {
+    start_timers();
+}
+
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    // Certified data does not survive an upgrade, so re-publish the certificate root.
+    backfill_certificate_tree();
+    update_certified_root();
+    backfill_connection_pairs();
+    backfill_user_indexes();
+    backfill_discovery_indexes();
+    backfill_group_indexes();
+    start_timers();
+}
+
+// Timers are not persisted across upgrades and must be re-armed on every install.
+fn start_timers() {
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(60 * 60), expire_connection_requests);
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(24 * 60 * 60), extend_session_series);
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(24 * 60 * 60), prune_group_activity_days);
+
+    let open_polls: Vec<GroupPoll> = GROUP_POLLS.with(|polls| {
+        polls.borrow().iter().map(|(_, poll)| poll).filter(|poll| poll.is_active).collect()
+    });
+    for poll in &open_polls {
+        schedule_poll_close(poll);
+    }
+}
 
 #[ic_cdk::query]
 fn get_self() -> Option<User> {
@@ -36,6 +156,7 @@ fn create_user(username: String, email: String) -> User {
         ai_interaction_style: "casual".to_string(),
         profile_visibility: "public".to_string(),
         activity_sharing: "connections".to_string(),
+        interests: vec![],
     };
 
     let new_user = User {
@@ -67,13 +188,161 @@ fn create_user(username: String, email: String) -> User {
         settings: default_settings,
     };
 
+    if let Some(existing) = USERS.with(|users| users.borrow().get(&principal)) {
+        unindex_user(&existing);
+    }
     USERS.with(|users| {
         users.borrow_mut().insert(principal, new_user.clone());
     });
+    index_user(&new_user);
 
     new_user
 }
 
+#[ic_cdk::update]
+fn update_interests(interests: Vec<String>) -> Result<User, String> {
+    let caller = ic_cdk::caller();
+    let mut interests: Vec<String> = interests.iter().filter_map(|i| normalize_topic(i)).collect();
+    interests.sort();
+    interests.dedup();
+    if interests.len() > 50 {
+        return Err("You can list at most 50 interests.".to_string());
+    }
+
+    let mut user = USERS.with(|users| users.borrow().get(&caller))
+        .ok_or("User not found.".to_string())?;
+    unindex_user(&user);
+    user.settings.interests = interests;
+    user.updated_at = ic_cdk::api::time();
+    USERS.with(|users| {
+        users.borrow_mut().insert(caller, user.clone());
+    });
+    index_user(&user);
+
+    Ok(user)
+}
+
+// Prefix search over usernames. Only profiles the caller is allowed to see are returned.
+#[ic_cdk::query]
+fn search_users(query: String, limit: u64) -> Vec<UserProfile> {
+    let caller = ic_cdk::caller();
+    let prefix = IndexTerm::new(&query.trim().to_lowercase());
+    if prefix.as_str().is_empty() {
+        return vec![];
+    }
+
+    USERS_BY_USERNAME.with(|index| {
+        index
+            .borrow()
+            .range((prefix.clone(), Principal::management_canister())..)
+            .take_while(|((username, _), _)| username.as_str().starts_with(prefix.as_str()))
+            .map(|((_, user_id), _)| user_id)
+            .filter(|user_id| *user_id != caller)
+            .filter_map(|user_id| USERS.with(|users| users.borrow().get(&user_id)))
+            .filter(|user| can_view_profile(caller, user))
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(UserProfile::from)
+            .collect()
+    })
+}
+
+// Ranks people the caller may know: friends of friends, study group mates and
+// users with shared interests. Only public, unblocked profiles that are not
+// already connected (or pending) are suggested.
+#[ic_cdk::query]
+fn suggest_connections(limit: u64) -> Vec<ConnectionSuggestion> {
+    let caller = ic_cdk::caller();
+    let me = match USERS.with(|users| users.borrow().get(&caller)) {
+        Some(user) => user,
+        None => return vec![],
+    };
+
+    let mut mutual: HashMap<Principal, u32> = HashMap::new();
+    for friend in connected_users(caller) {
+        for candidate in connected_users(friend) {
+            *mutual.entry(candidate).or_default() += 1;
+        }
+    }
+
+    let mut shared_groups: HashMap<Principal, u32> = HashMap::new();
+    for group_id in active_group_ids(caller) {
+        for candidate in active_member_ids(group_id) {
+            *shared_groups.entry(candidate).or_default() += 1;
+        }
+    }
+
+    let mut shared_interests: HashMap<Principal, Vec<String>> = HashMap::new();
+    for interest in &me.settings.interests {
+        let users: Vec<Principal> = USERS_BY_INTEREST.with(|index| {
+            index
+                .borrow()
+                .range((IndexTerm::new(interest), Principal::management_canister())..)
+                .take_while(|((i, _), _)| *i == IndexTerm::new(interest))
+                .map(|((_, user_id), _)| user_id)
+                .collect()
+        });
+        for candidate in users {
+            shared_interests.entry(candidate).or_default().push(interest.clone());
+        }
+    }
+
+    let mut candidates: Vec<Principal> = mutual
+        .keys()
+        .chain(shared_groups.keys())
+        .chain(shared_interests.keys())
+        .copied()
+        .filter(|candidate| *candidate != caller)
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+
+    let mut suggestions: Vec<ConnectionSuggestion> = candidates
+        .into_iter()
+        .filter(|candidate| {
+            !is_blocked_between(caller, *candidate)
+                && !CONNECTION_PAIRS.with(|pairs| pairs.borrow().contains_key(&pair_key(caller, *candidate)))
+        })
+        .filter_map(|candidate| USERS.with(|users| users.borrow().get(&candidate)))
+        .filter(|user| user.is_active && user.settings.profile_visibility == "public")
+        .map(|user| {
+            let mutual_connections = mutual.get(&user.id).copied().unwrap_or(0);
+            let groups = shared_groups.get(&user.id).copied().unwrap_or(0);
+            let interests = shared_interests.get(&user.id).cloned().unwrap_or_default();
+
+            let mut score = 0.0f32;
+            let mut reasons = vec![];
+            if groups > 0 {
+                score += 2.0 * groups as f32;
+                reasons.push(format!("{} shared study groups", groups));
+            }
+            if mutual_connections > 0 {
+                score += 1.5 * mutual_connections as f32;
+                reasons.push(format!("{} mutual connections", mutual_connections));
+            }
+            if !interests.is_empty() {
+                score += interests.len() as f32;
+                reasons.push(format!("Also interested in {}", interests.join(", ")));
+            }
+            if user.settings.difficulty_level == me.settings.difficulty_level {
+                score += 1.0;
+                reasons.push(format!("Also learning at {} level", user.settings.difficulty_level));
+            }
+
+            ConnectionSuggestion {
+                profile: user.into(),
+                score,
+                mutual_connections,
+                shared_groups: groups,
+                reasons,
+            }
+        })
+        .collect();
+
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+    suggestions.truncate(limit.min(MAX_PAGE_SIZE) as usize);
+    suggestions
+}
+
 #[ic_cdk::update]
 fn create_tutor(
     name: String,
@@ -101,15 +370,44 @@ fn create_tutor(
         voice_settings: HashMap::new(),
         created_at: ic_cdk::api::time(),
         updated_at: ic_cdk::api::time(),
+        topic_ids: vec![],
+        is_public: false,
     };
 
     TUTORS.with(|tutors| {
         tutors.borrow_mut().insert(tutor_id, new_tutor.clone());
     });
+    TUTORS_BY_USER.with(|index| {
+        index.borrow_mut().insert((caller, tutor_id), ());
+    });
 
     new_tutor
 }
 
+// Publishing a tutor announces it to the owner's followers the first time it goes public.
+#[ic_cdk::update]
+fn set_tutor_visibility(tutor_id: u64, is_public: bool) -> Result<Tutor, String> {
+    let caller = ic_cdk::caller();
+    let mut tutor = TUTORS.with(|tutors| tutors.borrow().get(&tutor_id))
+        .ok_or("Tutor not found.".to_string())?;
+    if tutor.user_id != caller {
+        return Err("Only the tutor's owner can change its visibility.".to_string());
+    }
+
+    let published = is_public && !tutor.is_public;
+    tutor.is_public = is_public;
+    tutor.updated_at = ic_cdk::api::time();
+    TUTORS.with(|tutors| {
+        tutors.borrow_mut().insert(tutor_id, tutor.clone());
+    });
+
+    if published {
+        record_event(caller, "tutor_published", tutor_id, None, tutor.name.clone());
+    }
+
+    Ok(tutor)
+}
+
 #[ic_cdk::query]
 fn get_tutor(id: u64) -> Option<Tutor> {
     TUTORS.with(|tutors| tutors.borrow().get(&id))
@@ -118,14 +416,169 @@ fn get_tutor(id: u64) -> Option<Tutor> {
 #[ic_cdk::query]
 fn get_tutors() -> Vec<Tutor> {
     let caller = ic_cdk::caller();
+    let ids = index_entries(&TUTORS_BY_USER, caller);
     TUTORS.with(|tutors| {
-        tutors
+        let tutors = tutors.borrow();
+        ids.iter().filter_map(|id| tutors.get(id)).collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_my_tutor_sessions() -> Vec<TutorSession> {
+    let caller = ic_cdk::caller();
+    let ids = index_entries(&TUTOR_SESSIONS_BY_USER, caller);
+    TUTOR_SESSIONS.with(|sessions| {
+        let sessions = sessions.borrow();
+        ids.iter().filter_map(|id| sessions.get(id)).collect()
+    })
+}
+
+// Starts a learning session with one of the caller's own tutors or a public one.
+#[ic_cdk::update]
+fn start_tutor_session(tutor_id: u64, topic: String) -> Result<TutorSession, String> {
+    let caller = ic_cdk::caller();
+    let tutor = TUTORS.with(|tutors| tutors.borrow().get(&tutor_id))
+        .ok_or("Tutor not found.".to_string())?;
+    if !tutor.is_public && tutor.user_id != caller {
+        return Err("Tutor not found.".to_string());
+    }
+    let topic = non_empty(topic).ok_or("Session topic cannot be empty.".to_string())?;
+
+    let now = ic_cdk::api::time();
+    let session_id = next_id("tutor_session");
+    let session = TutorSession {
+        id: session_id,
+        public_id: session_id.to_string(),
+        user_id: caller,
+        tutor_id,
+        topic,
+        status: "active".to_string(),
+        created_at: now,
+        updated_at: now,
+        messages: vec![],
+    };
+    TUTOR_SESSIONS.with(|sessions| {
+        sessions.borrow_mut().insert(session_id, session.clone());
+    });
+    TUTOR_SESSIONS_BY_USER.with(|index| {
+        index.borrow_mut().insert((caller, session_id), ());
+    });
+
+    Ok(session)
+}
+
+// Stores a course generated in one of the caller's tutor sessions.
+#[ic_cdk::update]
+fn create_tutor_course(session_id: u64, course: NewTutorCourse) -> Result<TutorCourse, String> {
+    let caller = ic_cdk::caller();
+    let session = owned_tutor_session(session_id, caller)?;
+    let topic = non_empty(course.topic).ok_or("Course topic cannot be empty.".to_string())?;
+    validate_learning_level(&course.difficulty_level)?;
+    if course.modules.is_empty() {
+        return Err("A course needs at least one module.".to_string());
+    }
+    let mut modules = vec![];
+    for (index, module) in course.modules.into_iter().enumerate() {
+        let title = non_empty(module.title).ok_or("Module titles cannot be empty.".to_string())?;
+        modules.push(CourseModule {
+            id: index as u64 + 1,
+            title,
+            description: module.description,
+            order: index as u32 + 1,
+            content: module.content,
+            status: "pending".to_string(),
+        });
+    }
+
+    let course_id = next_id("tutor_course");
+    let new_course = TutorCourse {
+        id: course_id,
+        tutor_id: session.tutor_id,
+        session_id,
+        topic,
+        outline: course.outline,
+        difficulty_level: course.difficulty_level,
+        estimated_duration: course.estimated_duration,
+        created_at: ic_cdk::api::time(),
+        modules,
+        completed_at: None,
+    };
+    TUTOR_COURSES.with(|courses| {
+        courses.borrow_mut().insert(course_id, new_course.clone());
+    });
+    COURSES_BY_SESSION.with(|index| {
+        index.borrow_mut().insert((session_id, course_id), ());
+    });
+
+    Ok(new_course)
+}
+
+#[ic_cdk::query]
+fn get_tutor_session_courses(session_id: u64) -> Result<Vec<TutorCourse>, String> {
+    let caller = ic_cdk::caller();
+    owned_tutor_session(session_id, caller)?;
+    let ids: Vec<u64> = COURSES_BY_SESSION.with(|index| {
+        index
             .borrow()
-            .iter()
-            .filter(|(_, tutor)| tutor.user_id == caller)
-            .map(|(_, tutor)| tutor.clone())
+            .range((session_id, 0)..=(session_id, u64::MAX))
+            .map(|((_, id), _)| id)
             .collect()
-    })
+    });
+    Ok(TUTOR_COURSES.with(|courses| {
+        let courses = courses.borrow();
+        ids.iter().filter_map(|id| courses.get(id)).collect()
+    }))
+}
+
+// Records that the learner finished a module. The course is completed, and a
+// certificate issued, once every module has been.
+#[ic_cdk::update]
+fn complete_course_module(course_id: u64, module_id: u64) -> Result<TutorCourse, String> {
+    let caller = ic_cdk::caller();
+    let mut course = TUTOR_COURSES.with(|courses| courses.borrow().get(&course_id))
+        .ok_or("Course not found.".to_string())?;
+    let session = owned_tutor_session(course.session_id, caller)?;
+    let module = course.modules.iter_mut().find(|m| m.id == module_id)
+        .ok_or("Module not found in this course.".to_string())?;
+    if module.status == "completed" {
+        return Err("You have already completed this module.".to_string());
+    }
+    module.status = "completed".to_string();
+
+    if course.completed_at.is_none() && course.modules.iter().all(|m| m.status == "completed") {
+        complete_tutor_course(session.user_id, &mut course);
+    }
+    TUTOR_COURSES.with(|courses| {
+        courses.borrow_mut().insert(course_id, course.clone());
+    });
+
+    Ok(course)
+}
+
+// Lets the tutor's owner mark a course completed, e.g. after an assessment
+// done outside the canister.
+#[ic_cdk::update]
+fn attest_tutor_course_completion(course_id: u64) -> Result<TutorCourse, String> {
+    let caller = ic_cdk::caller();
+    let mut course = TUTOR_COURSES.with(|courses| courses.borrow().get(&course_id))
+        .ok_or("Course not found.".to_string())?;
+    let tutor = TUTORS.with(|tutors| tutors.borrow().get(&course.tutor_id))
+        .ok_or("Tutor not found.".to_string())?;
+    if tutor.user_id != caller && !is_admin(caller) {
+        return Err("Only the tutor's owner or an admin can attest completion.".to_string());
+    }
+    if course.completed_at.is_some() {
+        return Err("This course has already been completed.".to_string());
+    }
+    let session = TUTOR_SESSIONS.with(|sessions| sessions.borrow().get(&course.session_id))
+        .ok_or("Tutor session not found.".to_string())?;
+
+    complete_tutor_course(session.user_id, &mut course);
+    TUTOR_COURSES.with(|courses| {
+        courses.borrow_mut().insert(course_id, course.clone());
+    });
+
+    Ok(course)
 }
 
 #[ic_cdk::update]
@@ -135,7 +588,31 @@ fn send_connection_request(receiver_id: Principal, message: Option<String>) -> R
         return Err("Cannot send connection request to yourself.".to_string());
     }
 
-    // TODO: Check if already connected or request already exists
+    if !USERS.with(|users| users.borrow().contains_key(&receiver_id)) {
+        return Err("User not found.".to_string());
+    }
+
+    if is_blocked_between(sender_id, receiver_id) {
+        return Err("You cannot connect with this user.".to_string());
+    }
+
+    let key = pair_key(sender_id, receiver_id);
+    let mut pair = CONNECTION_PAIRS.with(|pairs| pairs.borrow().get(&key)).unwrap_or_default();
+
+    if pair.connection_id.is_some() {
+        return Err("You are already connected with this user.".to_string());
+    }
+
+    if let Some(pending_id) = pair.pending_request_id {
+        let pending = CONNECTION_REQUESTS.with(|requests| requests.borrow().get(&pending_id))
+            .ok_or("Connection request not found.".to_string())?;
+        if pending.sender_id == sender_id {
+            return Err("A connection request to this user is already pending.".to_string());
+        }
+        // The receiver already asked to connect with us, so treat this as an acceptance.
+        let (accepted, _) = accept_request(pending);
+        return Ok(accepted);
+    }
 
     let request_id = next_id("connection_request");
     let new_request = ConnectionRequest {
@@ -152,6 +629,12 @@ fn send_connection_request(receiver_id: Principal, message: Option<String>) -> R
     CONNECTION_REQUESTS.with(|requests| {
         requests.borrow_mut().insert(request_id, new_request.clone());
     });
+    index_request(&new_request);
+
+    pair.pending_request_id = Some(request_id);
+    CONNECTION_PAIRS.with(|pairs| {
+        pairs.borrow_mut().insert(key, pair);
+    });
 
     Ok(new_request)
 }
@@ -171,284 +654,6247 @@ fn accept_connection_request(request_id: u64) -> Result<UserConnection, String>
         return Err("This request is no longer pending.".to_string());
     }
 
-    // Update request status
-    let updated_request = ConnectionRequest {
-        status: "accepted".to_string(),
-        responded_at: Some(ic_cdk::api::time()),
-        ..request
-    };
-    CONNECTION_REQUESTS.with(|requests| {
-        requests.borrow_mut().insert(request_id, updated_request);
-    });
-
-    // Create a new connection
-    let connection_id = next_id("connection");
-    let new_connection = UserConnection {
-        id: connection_id,
-        user1_id: request.sender_id,
-        user2_id: request.receiver_id,
-        status: "active".to_string(),
-        created_at: ic_cdk::api::time(),
-        updated_at: ic_cdk::api::time(),
-    };
+    if is_blocked_between(request.sender_id, request.receiver_id) {
+        return Err("You cannot connect with this user.".to_string());
+    }
 
-    CONNECTIONS.with(|connections| {
-        connections.borrow_mut().insert(connection_id, new_connection.clone());
-    });
-    
-    Ok(new_connection)
-}
+    // The expiry timer runs hourly; don't let a stale request slip through in between.
+    if request.created_at + CONNECTION_REQUEST_TTL < ic_cdk::api::time() {
+        close_request(request, "expired");
+        return Err("This request has expired.".to_string());
+    }
 
-#[ic_cdk::query]
-fn get_connections() -> Vec<UserConnection> {
-    let caller = ic_cdk::caller();
-    CONNECTIONS.with(|connections| {
-        connections
-            .borrow()
-            .iter()
-            .filter(|(_, conn)| conn.user1_id == caller || conn.user2_id == caller)
-            .map(|(_, conn)| conn.clone())
-            .collect()
-    })
+    let (_, connection) = accept_request(request);
+    Ok(connection)
 }
 
 #[ic_cdk::update]
-fn create_study_group(
-    name: String,
-    description: Option<String>,
-    is_private: bool,
-    max_members: u32,
-    learning_level: String,
-) -> Result<StudyGroup, String> {
+fn reject_connection_request(request_id: u64) -> Result<ConnectionRequest, String> {
     let caller = ic_cdk::caller();
-    let group_id = next_id("study_group");
 
-    let new_group = StudyGroup {
-        id: group_id,
-        public_id: group_id.to_string(),
-        name,
-        description,
-        creator_id: caller,
-        topic_id: None, // Can be set later
-        is_private,
-        max_members,
-        learning_level,
-        meeting_frequency: None,
-        goals: None,
-        created_at: ic_cdk::api::time(),
-        updated_at: ic_cdk::api::time(),
-    };
+    let request = CONNECTION_REQUESTS.with(|requests| requests.borrow().get(&request_id))
+        .ok_or("Connection request not found.".to_string())?;
 
-    STUDY_GROUPS.with(|groups| {
-        groups.borrow_mut().insert(group_id, new_group.clone());
-    });
-    
-    // Automatically add the creator as the first member and admin
-    let membership_id = next_id("group_membership");
-    let new_membership = GroupMembership {
-        id: membership_id,
-        user_id: caller,
-        group_id,
-        role: "admin".to_string(),
-        status: "active".to_string(),
-        joined_at: ic_cdk::api::time(),
-        contributions: 0,
-        last_active_at: Some(ic_cdk::api::time()),
-    };
+    if request.receiver_id != caller {
+        return Err("You are not authorized to reject this request.".to_string());
+    }
 
-    GROUP_MEMBERSHIPS.with(|memberships| {
-        memberships.borrow_mut().insert(membership_id, new_membership);
-    });
+    if request.status != "pending" {
+        return Err("This request is no longer pending.".to_string());
+    }
 
-    Ok(new_group)
+    Ok(close_request(request, "rejected"))
 }
 
 #[ic_cdk::update]
-fn join_study_group(group_id: u64) -> Result<GroupMembership, String> {
+fn cancel_connection_request(request_id: u64) -> Result<ConnectionRequest, String> {
     let caller = ic_cdk::caller();
-    
-    // Check if group exists
-    let _group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))
-        .ok_or("Study group not found.".to_string())?;
 
-    // TODO: Add checks for private groups, max members, etc.
-    
-    let membership_id = next_id("group_membership");
-    let new_membership = GroupMembership {
-        id: membership_id,
-        user_id: caller,
-        group_id,
-        role: "member".to_string(),
-        status: "active".to_string(),
-        joined_at: ic_cdk::api::time(),
-        contributions: 0,
-        last_active_at: Some(ic_cdk::api::time()),
-    };
+    let request = CONNECTION_REQUESTS.with(|requests| requests.borrow().get(&request_id))
+        .ok_or("Connection request not found.".to_string())?;
 
-    GROUP_MEMBERSHIPS.with(|memberships| {
-        memberships.borrow_mut().insert(membership_id, new_membership.clone());
-    });
+    if request.sender_id != caller {
+        return Err("You are not authorized to cancel this request.".to_string());
+    }
+
+    if request.status != "pending" {
+        return Err("This request is no longer pending.".to_string());
+    }
 
-    Ok(new_membership)
+    Ok(close_request(request, "cancelled"))
 }
 
+// Requests received by the caller, newest first.
 #[ic_cdk::query]
-fn get_study_group(id: u64) -> Option<StudyGroup> {
-    STUDY_GROUPS.with(|groups| groups.borrow().get(&id))
+fn get_incoming_requests(status: Option<String>, offset: u64, limit: u64) -> Vec<ConnectionRequest> {
+    let caller = ic_cdk::caller();
+    list_requests(&REQUESTS_BY_RECEIVER, caller, status, offset, limit)
+}
+
+// Requests sent by the caller, newest first.
+#[ic_cdk::query]
+fn get_outgoing_requests(status: Option<String>, offset: u64, limit: u64) -> Vec<ConnectionRequest> {
+    let caller = ic_cdk::caller();
+    list_requests(&REQUESTS_BY_SENDER, caller, status, offset, limit)
 }
 
 #[ic_cdk::update]
-fn create_task(
-    title: String,
-    description: String,
-    category: String,
-    difficulty: String,
-    token_reward: u32,
-    points_reward: u32,
-) -> Result<Task, String> {
+fn remove_connection(connection_id: u64) -> Result<(), String> {
     let caller = ic_cdk::caller();
-    // TODO: Add check to ensure caller is an admin
 
-    let task_id = next_id("task");
-    let new_task = Task {
-        id: task_id,
-        public_id: task_id.to_string(),
-        title,
-        description,
-        category,
-        difficulty,
-        token_reward,
-        points_reward,
-        requirements: None,
-        is_active: true,
-        is_repeatable: false,
-        max_completions: 1,
-        created_by: caller,
-        created_at: ic_cdk::api::time(),
-        expires_at: None,
-        metadata: None,
-    };
+    let connection = CONNECTIONS.with(|connections| connections.borrow().get(&connection_id))
+        .filter(|connection| connection.status == "active")
+        .ok_or("Connection not found.".to_string())?;
 
-    TASKS.with(|tasks| {
-        tasks.borrow_mut().insert(task_id, new_task.clone());
-    });
+    if connection.user1_id != caller && connection.user2_id != caller {
+        return Err("You are not authorized to remove this connection.".to_string());
+    }
 
-    Ok(new_task)
+    end_connection(connection);
+    Ok(())
 }
 
+// --- Blocking Methods ---
+
+// Blocking ends any connection or pending request between the two users.
 #[ic_cdk::update]
-fn complete_task(task_id: u64) -> Result<UserTaskCompletion, String> {
+fn block_user(user_id: Principal) -> Result<(), String> {
     let caller = ic_cdk::caller();
-    
-    let task = TASKS.with(|tasks| tasks.borrow().get(&task_id))
-        .ok_or("Task not found.".to_string())?;
+    if caller == user_id {
+        return Err("You cannot block yourself.".to_string());
+    }
+    if BLOCKS.with(|blocks| blocks.borrow().contains_key(&(caller, user_id))) {
+        return Err("You have already blocked this user.".to_string());
+    }
 
-    // TODO: Add validation to check if user has already completed the task
+    let pair = CONNECTION_PAIRS.with(|pairs| pairs.borrow().get(&pair_key(caller, user_id)));
+    if let Some(pair) = pair {
+        if let Some(request_id) = pair.pending_request_id {
+            if let Some(request) = CONNECTION_REQUESTS.with(|requests| requests.borrow().get(&request_id)) {
+                let status = if request.sender_id == caller { "cancelled" } else { "rejected" };
+                close_request(request, status);
+            }
+        }
+        if let Some(connection_id) = pair.connection_id {
+            if let Some(connection) = CONNECTIONS.with(|connections| connections.borrow().get(&connection_id)) {
+                end_connection(connection);
+            }
+        }
+    }
 
-    let completion_id = next_id("user_task_completion");
-    let new_completion = UserTaskCompletion {
-        id: completion_id,
-        user_id: caller,
-        task_id,
-        completed_at: ic_cdk::api::time(),
-        tokens_earned: task.token_reward,
-        points_earned: task.points_reward,
-        completion_count: 1,
-        proof_data: None,
-        metadata: None,
-    };
+    unfollow(caller, user_id);
+    unfollow(user_id, caller);
 
-    USER_TASK_COMPLETIONS.with(|completions| {
-        completions.borrow_mut().insert(completion_id, new_completion.clone());
+    BLOCKS.with(|blocks| {
+        blocks.borrow_mut().insert((caller, user_id), ic_cdk::api::time());
     });
 
-    // TODO: Update user's token/point balance
+    Ok(())
+}
 
-    Ok(new_completion)
+#[ic_cdk::update]
+fn unblock_user(user_id: Principal) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    BLOCKS.with(|blocks| blocks.borrow_mut().remove(&(caller, user_id)))
+        .map(|_| ())
+        .ok_or("This user is not blocked.".to_string())
 }
 
 #[ic_cdk::query]
-fn get_tasks() -> Vec<Task> {
-    TASKS.with(|tasks| {
-        tasks.borrow().iter().map(|(_, task)| task.clone()).collect()
+fn get_blocked_users() -> Vec<Principal> {
+    let caller = ic_cdk::caller();
+    BLOCKS.with(|blocks| {
+        blocks
+            .borrow()
+            .range((caller, Principal::management_canister())..)
+            .take_while(|((blocker, _), _)| *blocker == caller)
+            .map(|((_, blocked), _)| blocked)
+            .collect()
     })
 }
 
-// --- Admin Methods ---
-
+// Returns another user's profile if their `profile_visibility` allows the caller to see it.
+// Blocked users are reported as not found so a block is not revealed.
 #[ic_cdk::query]
-fn get_all_users_admin() -> Result<Vec<User>, String> {
-    if !is_admin(ic_cdk::caller()) {
-        return Err("Only admins can perform this action.".to_string());
+fn get_user_profile(user_id: Principal) -> Result<UserProfile, String> {
+    let caller = ic_cdk::caller();
+    let user = USERS.with(|users| users.borrow().get(&user_id))
+        .ok_or("User not found.".to_string())?;
+
+    if !can_view_profile(caller, &user) {
+        return Err("User not found.".to_string());
     }
-    Ok(USERS.with(|users| users.borrow().iter().map(|(_, user)| user.clone()).collect()))
+
+    Ok(user.into())
 }
 
+// --- Follow Methods ---
+
+// Following is one-directional and needs no approval, unlike connections.
 #[ic_cdk::update]
-fn update_user_status_admin(user_id: Principal, status: String) -> Result<User, String> {
-    if !is_admin(ic_cdk::caller()) {
-        return Err("Only admins can perform this action.".to_string());
+fn follow_user(user_id: Principal) -> Result<FollowStats, String> {
+    let caller = ic_cdk::caller();
+    if caller == user_id {
+        return Err("You cannot follow yourself.".to_string());
     }
-    
-    USERS.with(|users| {
-        let mut users_mut = users.borrow_mut();
-        if let Some(mut user) = users_mut.get(&user_id) {
-            user.status = status;
-            users_mut.insert(user_id, user.clone());
-            Ok(user)
-        } else {
-            Err("User not found.".to_string())
-        }
-    })
+    let user = USERS.with(|users| users.borrow().get(&user_id))
+        .ok_or("User not found.".to_string())?;
+    if !can_view_profile(caller, &user) {
+        return Err("User not found.".to_string());
+    }
+    if FOLLOWS.with(|follows| follows.borrow().contains_key(&(caller, user_id))) {
+        return Err("You are already following this user.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    FOLLOWS.with(|follows| {
+        follows.borrow_mut().insert((caller, user_id), now);
+    });
+    FOLLOWERS.with(|followers| {
+        followers.borrow_mut().insert((user_id, caller), now);
+    });
+
+    Ok(follow_stats(caller, user_id))
 }
 
-// --- Billing Methods (Placeholders) ---
+#[ic_cdk::update]
+fn unfollow_user(user_id: Principal) -> Result<FollowStats, String> {
+    let caller = ic_cdk::caller();
+    if !unfollow(caller, user_id) {
+        return Err("You are not following this user.".to_string());
+    }
+    Ok(follow_stats(caller, user_id))
+}
+
+#[ic_cdk::query]
+fn get_follow_stats(user_id: Principal) -> Result<FollowStats, String> {
+    let caller = ic_cdk::caller();
+    let user = USERS.with(|users| users.borrow().get(&user_id))
+        .ok_or("User not found.".to_string())?;
+    if !can_view_profile(caller, &user) {
+        return Err("User not found.".to_string());
+    }
+    Ok(follow_stats(caller, user_id))
+}
+
+#[ic_cdk::query]
+fn get_followers(user_id: Principal, offset: u64, limit: u64) -> Result<Vec<UserProfile>, String> {
+    list_follows(&FOLLOWERS, user_id, offset, limit)
+}
+
+#[ic_cdk::query]
+fn get_following(user_id: Principal, offset: u64, limit: u64) -> Result<Vec<UserProfile>, String> {
+    list_follows(&FOLLOWS, user_id, offset, limit)
+}
+
+// Newest-first public events (published tutors, completed paths, unlocked
+// achievements) from the users the caller follows.
+#[ic_cdk::query]
+fn get_following_feed(cursor: Option<u64>, limit: u64) -> FeedPage {
+    let caller = ic_cdk::caller();
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+    let before = cursor.unwrap_or(u64::MAX);
+
+    let pages = follow_entries(&FOLLOWS, caller)
+        .into_iter()
+        .filter(|followee| can_view_activity(caller, *followee))
+        .map(|followee| {
+            actor_events(followee, before, limit + 1, |event| {
+                is_follow_feed_event(event) && event_group_visible(caller, event)
+            })
+        })
+        .collect();
+    merge_feed_pages(pages, limit)
+}
+
+// --- Feed Methods ---
+
+// Newest-first activity from the caller's connections and from the study groups
+// they belong to, filtered by each actor's `activity_sharing` setting.
+// Connections' events inside private groups are only shown to members.
+#[ic_cdk::query]
+fn get_feed(cursor: Option<u64>, limit: u64) -> FeedPage {
+    feed_page(ic_cdk::caller(), cursor, limit)
+}
+
+// --- Direct Message Methods ---
 
-// TODO: Implement full logic for creating subscription plans
 #[ic_cdk::update]
-fn create_subscription_plan_admin(/* params */) -> Result<(), String> {
-    if !is_admin(ic_cdk::caller()) {
-        return Err("Only admins can perform this action.".to_string());
+fn send_direct_message(recipient_id: Principal, content: String) -> Result<DirectMessage, String> {
+    let caller = ic_cdk::caller();
+    if caller == recipient_id {
+        return Err("You cannot message yourself.".to_string());
     }
-    // Placeholder
-    Ok(())
+    let content = validate_message_content(content)?;
+    if is_blocked_between(caller, recipient_id) || !is_connected(caller, recipient_id) {
+        return Err("You can only message your connections.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    let key = pair_key(caller, recipient_id);
+    let mut conversation = match CONVERSATION_PAIRS.with(|pairs| pairs.borrow().get(&key)) {
+        Some(id) => CONVERSATIONS.with(|conversations| conversations.borrow().get(&id))
+            .ok_or("Conversation not found.".to_string())?,
+        None => {
+            let conversation_id = next_id("conversation");
+            CONVERSATION_PAIRS.with(|pairs| {
+                pairs.borrow_mut().insert(key, conversation_id);
+            });
+            CONVERSATIONS_BY_USER.with(|index| {
+                let mut index = index.borrow_mut();
+                index.insert((key.0, conversation_id), ());
+                index.insert((key.1, conversation_id), ());
+            });
+            Conversation {
+                id: conversation_id,
+                user1_id: key.0,
+                user2_id: key.1,
+                last_seq: 0,
+                user1_last_read_seq: 0,
+                user2_last_read_seq: 0,
+                created_at: now,
+                updated_at: now,
+            }
+        }
+    };
+
+    conversation.last_seq += 1;
+    conversation.updated_at = now;
+    // Sending implies the sender has read everything up to their own message.
+    if conversation.user1_id == caller {
+        conversation.user1_last_read_seq = conversation.last_seq;
+    } else {
+        conversation.user2_last_read_seq = conversation.last_seq;
+    }
+
+    let message = DirectMessage {
+        conversation_id: conversation.id,
+        seq: conversation.last_seq,
+        sender_id: caller,
+        content,
+        sent_at: now,
+        is_deleted: false,
+        deleted_at: None,
+    };
+
+    DIRECT_MESSAGES.with(|messages| {
+        messages.borrow_mut().insert((conversation.id, message.seq), message.clone());
+    });
+    CONVERSATIONS.with(|conversations| {
+        conversations.borrow_mut().insert(conversation.id, conversation);
+    });
+
+    Ok(message)
 }
 
-// TODO: Implement logic for creating a new subscription (HTTPS outcall to Paystack)
+// The caller's conversations, most recently active first.
+#[ic_cdk::query]
+fn get_conversations() -> Vec<ConversationSummary> {
+    let caller = ic_cdk::caller();
+    let ids = index_entries(&CONVERSATIONS_BY_USER, caller);
+    let mut summaries: Vec<ConversationSummary> = ids
+        .into_iter()
+        .filter_map(|id| CONVERSATIONS.with(|conversations| conversations.borrow().get(&id)))
+        .map(|conversation| {
+            let other_user_id = if conversation.user1_id == caller { conversation.user2_id } else { conversation.user1_id };
+            let last_message = DIRECT_MESSAGES.with(|messages| {
+                messages.borrow().get(&(conversation.id, conversation.last_seq))
+            });
+            ConversationSummary {
+                unread_count: unread_count(&conversation, caller),
+                conversation,
+                other_user_id,
+                last_message,
+            }
+        })
+        .collect();
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.conversation.updated_at));
+    summaries
+}
+
+// A page of messages older than `before_seq` (or the latest ones), newest first.
+#[ic_cdk::query]
+fn get_direct_messages(conversation_id: u64, before_seq: Option<u64>, limit: u64) -> Result<Vec<DirectMessage>, String> {
+    let caller = ic_cdk::caller();
+    let conversation = participant_conversation(conversation_id, caller)?;
+    let before = before_seq.unwrap_or(conversation.last_seq + 1);
+
+    Ok(DIRECT_MESSAGES.with(|messages| {
+        messages
+            .borrow()
+            .range((conversation_id, 0)..(conversation_id, before))
+            .rev()
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(_, message)| message)
+            .collect()
+    }))
+}
+
+// Records that the caller has read the conversation up to `seq`. The other
+// participant sees this through the conversation's last-read sequence numbers.
 #[ic_cdk::update]
-fn create_subscription(/* params */) -> Result<(), String> {
-    // Placeholder
-    Ok(())
+fn mark_conversation_read(conversation_id: u64, seq: u64) -> Result<Conversation, String> {
+    let caller = ic_cdk::caller();
+    let mut conversation = participant_conversation(conversation_id, caller)?;
+    let seq = seq.min(conversation.last_seq);
+
+    let last_read = if conversation.user1_id == caller {
+        &mut conversation.user1_last_read_seq
+    } else {
+        &mut conversation.user2_last_read_seq
+    };
+    *last_read = (*last_read).max(seq);
+
+    CONVERSATIONS.with(|conversations| {
+        conversations.borrow_mut().insert(conversation_id, conversation.clone());
+    });
+
+    Ok(conversation)
 }
 
+#[ic_cdk::update]
+fn delete_direct_message(conversation_id: u64, seq: u64) -> Result<DirectMessage, String> {
+    let caller = ic_cdk::caller();
+    participant_conversation(conversation_id, caller)?;
+
+    let mut message = DIRECT_MESSAGES.with(|messages| messages.borrow().get(&(conversation_id, seq)))
+        .ok_or("Message not found.".to_string())?;
+    if message.sender_id != caller {
+        return Err("You can only delete your own messages.".to_string());
+    }
+    if message.is_deleted {
+        return Err("This message has already been deleted.".to_string());
+    }
 
-// --- Blockchain Methods (Placeholders) ---
+    message.content = String::new();
+    message.is_deleted = true;
+    message.deleted_at = Some(ic_cdk::api::time());
+    DIRECT_MESSAGES.with(|messages| {
+        messages.borrow_mut().insert((conversation_id, seq), message.clone());
+    });
+
+    Ok(message)
+}
 
-// TODO: Implement logic for fetching wallet balance (HTTPS outcall to Sui network)
 #[ic_cdk::query]
-fn get_sui_wallet_balance(wallet_address: String) -> Result<u64, String> {
-    // Placeholder
-    Ok(0)
+fn get_unread_message_count() -> u64 {
+    let caller = ic_cdk::caller();
+    index_entries(&CONVERSATIONS_BY_USER, caller)
+        .into_iter()
+        .filter_map(|id| CONVERSATIONS.with(|conversations| conversations.borrow().get(&id)))
+        .map(|conversation| unread_count(&conversation, caller))
+        .sum()
+}
+
+#[ic_cdk::query]
+fn get_connections() -> Vec<UserConnection> {
+    let caller = ic_cdk::caller();
+    let ids = index_entries(&CONNECTIONS_BY_USER, caller);
+    CONNECTIONS.with(|connections| {
+        let connections = connections.borrow();
+        ids.iter().filter_map(|id| connections.get(id)).collect()
+    })
 }
 
-// TODO: Implement ZK proof verification logic
 #[ic_cdk::update]
-fn verify_zk_proof(/* params */) -> Result<bool, String> {
-    // Placeholder
-    Ok(true)
+fn create_study_group(
+    name: String,
+    description: Option<String>,
+    is_private: bool,
+    max_members: u32,
+    learning_level: String,
+) -> Result<StudyGroup, String> {
+    let caller = ic_cdk::caller();
+    let name = validate_group_name(name)?;
+    validate_learning_level(&learning_level)?;
+    if max_members < 1 {
+        return Err("A group must allow at least one member.".to_string());
+    }
+    let group_id = next_id("study_group");
+
+    let new_group = StudyGroup {
+        id: group_id,
+        public_id: group_id.to_string(),
+        name,
+        description,
+        creator_id: caller,
+        topic_id: None, // Can be set later
+        is_private,
+        max_members,
+        learning_level,
+        meeting_frequency: None,
+        goals: None,
+        created_at: ic_cdk::api::time(),
+        updated_at: ic_cdk::api::time(),
+        is_archived: false,
+        archived_at: None,
+    };
+
+    STUDY_GROUPS.with(|groups| {
+        groups.borrow_mut().insert(group_id, new_group.clone());
+    });
+    index_group(&new_group);
+    
+    // Automatically add the creator as the first member and admin
+    let membership_id = next_id("group_membership");
+    let new_membership = GroupMembership {
+        id: membership_id,
+        user_id: caller,
+        group_id,
+        role: "admin".to_string(),
+        status: "active".to_string(),
+        joined_at: ic_cdk::api::time(),
+        contributions: 0,
+        last_active_at: Some(ic_cdk::api::time()),
+    };
+
+    insert_membership(new_membership);
+    log_group_activity(group_id, caller, "joined", None, None, None);
+
+    Ok(new_group)
 }
 
-// --- Private Helper Functions ---
+// Public groups can be joined directly. Private groups need an approved join
+// request or an invite link.
+#[ic_cdk::update]
+fn join_study_group(group_id: u64) -> Result<GroupMembership, String> {
+    let caller = ic_cdk::caller();
+    
+    // Check if group exists
+    let group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))
+        .ok_or("Study group not found.".to_string())?;
 
-fn is_admin(principal: Principal) -> bool {
-    USERS.with(|users| {
-        if let Some(user) = users.borrow().get(&principal) {
-            user.role == "admin"
-        } else {
-            false
-        }
-    })
+    if group.is_private {
+        return Err("This group is private. Send a join request or use an invite link.".to_string());
+    }
+
+    admit_member(&group, caller)
 }
 
-// --- Candid Generation ---
-ic_cdk::export_candid!();
+#[ic_cdk::update]
+fn request_to_join_group(group_id: u64, message: Option<String>) -> Result<GroupJoinRequest, String> {
+    let caller = ic_cdk::caller();
+    let group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))
+        .ok_or("Study group not found.".to_string())?;
+
+    if !group.is_private {
+        return Err("This group is public. Join it directly.".to_string());
+    }
+    check_can_join(&group, caller)?;
+    if PENDING_JOIN_REQUESTS.with(|pending| pending.borrow().contains_key(&(group_id, caller))) {
+        return Err("You already have a pending request for this group.".to_string());
+    }
+
+    let request_id = next_id("group_join_request");
+    let request = GroupJoinRequest {
+        id: request_id,
+        group_id,
+        user_id: caller,
+        status: "pending".to_string(),
+        message,
+        created_at: ic_cdk::api::time(),
+        responded_at: None,
+        responded_by: None,
+    };
+
+    GROUP_JOIN_REQUESTS.with(|requests| {
+        requests.borrow_mut().insert(request_id, request.clone());
+    });
+    PENDING_JOIN_REQUESTS.with(|pending| {
+        pending.borrow_mut().insert((group_id, caller), request_id);
+    });
+
+    Ok(request)
+}
+
+#[ic_cdk::update]
+fn approve_join_request(request_id: u64) -> Result<GroupMembership, String> {
+    let caller = ic_cdk::caller();
+    let request = pending_join_request(request_id)?;
+    if !is_group_admin(request.group_id, caller) {
+        return Err("Only group admins can approve join requests.".to_string());
+    }
+    let group = STUDY_GROUPS.with(|groups| groups.borrow().get(&request.group_id))
+        .ok_or("Study group not found.".to_string())?;
+
+    let membership = admit_member(&group, request.user_id)?;
+    close_join_request(request, "approved", Some(caller));
+
+    Ok(membership)
+}
+
+#[ic_cdk::update]
+fn reject_join_request(request_id: u64) -> Result<GroupJoinRequest, String> {
+    let caller = ic_cdk::caller();
+    let request = pending_join_request(request_id)?;
+    if !is_group_admin(request.group_id, caller) {
+        return Err("Only group admins can reject join requests.".to_string());
+    }
+    Ok(close_join_request(request, "rejected", Some(caller)))
+}
+
+#[ic_cdk::update]
+fn cancel_join_request(request_id: u64) -> Result<GroupJoinRequest, String> {
+    let caller = ic_cdk::caller();
+    let request = pending_join_request(request_id)?;
+    if request.user_id != caller {
+        return Err("You can only cancel your own join requests.".to_string());
+    }
+    Ok(close_join_request(request, "cancelled", None))
+}
+
+// Pending join requests for a group, visible to its admins.
+#[ic_cdk::query]
+fn get_group_join_requests(group_id: u64) -> Result<Vec<GroupJoinRequest>, String> {
+    let caller = ic_cdk::caller();
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can view join requests.".to_string());
+    }
+
+    let request_ids: Vec<u64> = PENDING_JOIN_REQUESTS.with(|pending| {
+        pending
+            .borrow()
+            .range((group_id, Principal::management_canister())..)
+            .take_while(|((group, _), _)| *group == group_id)
+            .map(|(_, request_id)| request_id)
+            .collect()
+    });
+    Ok(GROUP_JOIN_REQUESTS.with(|requests| {
+        let requests = requests.borrow();
+        request_ids.iter().filter_map(|id| requests.get(id)).collect()
+    }))
+}
+
+// Creates an invite link token for a group. Tokens come from the management
+// canister's randomness so they cannot be guessed.
+#[ic_cdk::update]
+async fn create_group_invite(group_id: u64, ttl_hours: u64, max_uses: Option<u32>) -> Result<GroupInvite, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can create invites.".to_string());
+    }
+    if ttl_hours == 0 || ttl_hours > MAX_INVITE_TTL_HOURS {
+        return Err(format!("Invites must expire within 1 to {} hours.", MAX_INVITE_TTL_HOURS));
+    }
+    if max_uses == Some(0) {
+        return Err("An invite must allow at least one use.".to_string());
+    }
+
+    let (bytes,) = ic_cdk::api::management_canister::main::raw_rand()
+        .await
+        .map_err(|(_, msg)| format!("Failed to generate invite token: {}", msg))?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let now = ic_cdk::api::time();
+    let invite = GroupInvite {
+        token: token.clone(),
+        group_id,
+        created_by: caller,
+        created_at: now,
+        expires_at: now + ttl_hours * NANOS_PER_HOUR,
+        max_uses,
+        uses: 0,
+        revoked: false,
+    };
+    GROUP_INVITES.with(|invites| {
+        invites.borrow_mut().insert(token.clone(), invite.clone());
+    });
+    INVITES_BY_GROUP.with(|index| {
+        index.borrow_mut().insert((group_id, IndexTerm::new(&token)), ());
+    });
+
+    Ok(invite)
+}
+
+#[ic_cdk::update]
+fn join_with_invite(token: String) -> Result<GroupMembership, String> {
+    let caller = ic_cdk::caller();
+    let mut invite = GROUP_INVITES.with(|invites| invites.borrow().get(&token))
+        .ok_or("Invite not found.".to_string())?;
+
+    if invite.revoked || invite.expires_at <= ic_cdk::api::time() {
+        return Err("This invite has expired.".to_string());
+    }
+    if invite.max_uses.is_some_and(|max| invite.uses >= max) {
+        return Err("This invite has reached its usage limit.".to_string());
+    }
+
+    let group = STUDY_GROUPS.with(|groups| groups.borrow().get(&invite.group_id))
+        .ok_or("Study group not found.".to_string())?;
+    let membership = admit_member(&group, caller)?;
+
+    invite.uses += 1;
+    GROUP_INVITES.with(|invites| {
+        invites.borrow_mut().insert(token, invite);
+    });
+    // An invite supersedes any pending request.
+    if let Some(request_id) = PENDING_JOIN_REQUESTS.with(|pending| pending.borrow().get(&(group.id, caller))) {
+        if let Some(request) = GROUP_JOIN_REQUESTS.with(|requests| requests.borrow().get(&request_id)) {
+            close_join_request(request, "cancelled", None);
+        }
+    }
+
+    Ok(membership)
+}
+
+#[ic_cdk::update]
+fn revoke_group_invite(token: String) -> Result<GroupInvite, String> {
+    let caller = ic_cdk::caller();
+    let mut invite = GROUP_INVITES.with(|invites| invites.borrow().get(&token))
+        .ok_or("Invite not found.".to_string())?;
+    if !is_group_admin(invite.group_id, caller) {
+        return Err("Only group admins can revoke invites.".to_string());
+    }
+
+    invite.revoked = true;
+    GROUP_INVITES.with(|invites| {
+        invites.borrow_mut().insert(token, invite.clone());
+    });
+
+    Ok(invite)
+}
+
+// Unexpired, unrevoked invites for a group, visible to its admins.
+#[ic_cdk::query]
+fn get_group_invites(group_id: u64) -> Result<Vec<GroupInvite>, String> {
+    let caller = ic_cdk::caller();
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can view invites.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    Ok(GROUP_INVITES.with(|invites| {
+        let invites = invites.borrow();
+        group_invite_tokens(group_id)
+            .iter()
+            .filter_map(|token| invites.get(token))
+            .filter(|invite| !invite.revoked && invite.expires_at > now)
+            .collect()
+    }))
+}
+
+#[ic_cdk::query]
+fn get_study_group(id: u64) -> Option<StudyGroup> {
+    STUDY_GROUPS.with(|groups| groups.borrow().get(&id))
+}
+
+#[ic_cdk::update]
+fn update_study_group(group_id: u64, update: StudyGroupUpdate) -> Result<StudyGroup, String> {
+    let caller = ic_cdk::caller();
+    let group = edit_study_group(group_id, caller, update, ic_cdk::api::time())?;
+    log_group_activity(group_id, caller, "group_updated", None, None, None);
+    Ok(group)
+}
+
+fn edit_study_group(group_id: u64, caller: Principal, update: StudyGroupUpdate, now: u64) -> Result<StudyGroup, String> {
+    let mut group = writable_group(group_id)?;
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can perform this action.".to_string());
+    }
+    let previous = group.clone();
+
+    if let Some(name) = update.name {
+        group.name = validate_group_name(name)?;
+    }
+    if let Some(description) = update.description {
+        group.description = non_empty(description);
+    }
+    if let Some(is_private) = update.is_private {
+        group.is_private = is_private;
+    }
+    if let Some(max_members) = update.max_members {
+        let member_count = active_memberships(group_id).len();
+        if (max_members as usize) < member_count.max(1) {
+            return Err(format!("max_members cannot be below the current member count ({}).", member_count));
+        }
+        group.max_members = max_members;
+    }
+    if let Some(learning_level) = update.learning_level {
+        validate_learning_level(&learning_level)?;
+        group.learning_level = learning_level;
+    }
+    if let Some(meeting_frequency) = update.meeting_frequency {
+        group.meeting_frequency = non_empty(meeting_frequency);
+    }
+    if let Some(goals) = update.goals {
+        group.goals = non_empty(goals);
+    }
+
+    group.updated_at = now;
+    STUDY_GROUPS.with(|groups| {
+        groups.borrow_mut().insert(group_id, group.clone());
+    });
+    unindex_group(&previous);
+    index_group(&group);
+
+    Ok(group)
+}
+
+// Archiving makes a group read-only: nobody can join and nothing in it can be
+// changed until an admin unarchives it. Members can still read and leave.
+#[ic_cdk::update]
+fn archive_study_group(group_id: u64) -> Result<StudyGroup, String> {
+    set_group_archived(group_id, true)
+}
+
+#[ic_cdk::update]
+fn unarchive_study_group(group_id: u64) -> Result<StudyGroup, String> {
+    set_group_archived(group_id, false)
+}
+
+// Permanently deletes a group and everything stored under it. Only the owner can do this.
+#[ic_cdk::update]
+fn delete_study_group(group_id: u64) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    let group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))
+        .ok_or("Study group not found.".to_string())?;
+    if group.creator_id != caller {
+        return Err("Only the group owner can delete the group.".to_string());
+    }
+
+    delete_group_data(&group);
+    unindex_group(&group);
+    remove_group_stats(group_id);
+    STUDY_GROUPS.with(|groups| {
+        groups.borrow_mut().remove(&group_id);
+    });
+
+    Ok(())
+}
+
+// Lists non-archived groups matching the filter, `limit` at a time.
+#[ic_cdk::query]
+fn list_study_groups(filter: StudyGroupFilter, offset: u64, limit: u64) -> Result<Vec<StudyGroupSummary>, String> {
+    let sort_by = filter.sort_by.clone().unwrap_or_else(|| "recent".to_string());
+    if !["recent", "members", "newest"].contains(&sort_by.as_str()) {
+        return Err("sort_by must be one of: recent, members, newest.".to_string());
+    }
+    let query_words = keywords(filter.query.as_deref().unwrap_or(""));
+    let offset = offset as usize;
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+    let matches = |summary: &StudyGroupSummary| matches_group_filter(summary, &filter, &query_words);
+
+    // Narrow the candidates with the most selective index available. Candidates
+    // come out of those indexes unsorted, so only the best `offset + limit` are
+    // kept while they stream past.
+    let summaries = if let Some(word) = query_words.first() {
+        GROUP_KEYWORDS.with(|index| {
+            // A group can match the prefix through several of its keywords.
+            let mut seen = HashSet::new();
+            let word = IndexTerm::new(word);
+            let index = index.borrow();
+            let candidates = index
+                .range((word.clone(), 0)..)
+                .take_while(|((keyword, _), _)| keyword.as_str().starts_with(word.as_str()))
+                .map(|((_, id), _)| id)
+                .filter(|id| seen.insert(*id))
+                .filter_map(group_summary)
+                .filter(|summary| matches(summary));
+            top_groups(candidates, &sort_by, offset + limit)
+        })
+    } else if let Some(topic_id) = filter.topic_id {
+        let candidates = topic_index_entries(&TOPIC_GROUPS, topic_id)
+            .into_iter()
+            .filter_map(group_summary)
+            .filter(|summary| matches(summary));
+        top_groups(candidates, &sort_by, offset + limit)
+    } else if let Some(level) = &filter.learning_level {
+        GROUPS_BY_LEVEL.with(|index| {
+            let level = IndexTerm::new(level);
+            let index = index.borrow();
+            let candidates = index
+                .range((level.clone(), 0)..=(level, u64::MAX))
+                .map(|((_, id), _)| id)
+                .filter_map(group_summary)
+                .filter(|summary| matches(summary));
+            top_groups(candidates, &sort_by, offset + limit)
+        })
+    } else {
+        // No filter index applies, so walk the sort index and stop once the page is full.
+        with_sorted_group_ids(&sort_by, |ids| {
+            ids.filter_map(group_summary).filter(|summary| matches(summary)).take(offset + limit).collect()
+        })
+    };
+
+    Ok(summaries.into_iter().skip(offset).take(limit).collect())
+}
+
+// Groups the caller is an active member of, most recently active first.
+#[ic_cdk::query]
+fn get_my_groups() -> Vec<StudyGroupSummary> {
+    let caller = ic_cdk::caller();
+    let mut summaries: Vec<StudyGroupSummary> = active_group_ids(caller)
+        .into_iter()
+        .filter_map(group_summary)
+        .collect();
+    summaries.sort_by_key(|s| std::cmp::Reverse(s.last_activity_at));
+    summaries
+}
+
+#[ic_cdk::query]
+fn get_group_members(group_id: u64) -> Result<Vec<GroupMembership>, String> {
+    let caller = ic_cdk::caller();
+    viewable_group(group_id, caller)?;
+    Ok(active_memberships(group_id))
+}
+
+// Leaving keeps the membership row as "inactive" so the user can rejoin later.
+// If the last admin leaves, the longest-standing moderator (or member) takes over,
+// and if the creator leaves, ownership passes to the longest-standing admin.
+#[ic_cdk::update]
+fn leave_study_group(group_id: u64) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    let membership = group_membership(group_id, caller)
+        .filter(|m| m.status == "active")
+        .ok_or("You are not a member of this group.".to_string())?;
+
+    insert_membership(GroupMembership {
+        role: "member".to_string(),
+        status: "inactive".to_string(),
+        ..membership
+    });
+    log_group_activity(group_id, caller, "left", None, None, None);
+
+    let mut remaining = active_memberships(group_id);
+    if remaining.is_empty() {
+        return Ok(());
+    }
+    remaining.sort_by_key(|m| (role_rank(&m.role), m.joined_at));
+
+    if !remaining.iter().any(|m| m.role == "admin") {
+        let successor = remaining[0].clone();
+        remaining[0] = GroupMembership { role: "admin".to_string(), ..successor };
+        insert_membership(remaining[0].clone());
+    }
+
+    let mut group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))
+        .ok_or("Study group not found.".to_string())?;
+    if group.creator_id == caller {
+        group.creator_id = remaining[0].user_id;
+        group.updated_at = ic_cdk::api::time();
+        STUDY_GROUPS.with(|groups| {
+            groups.borrow_mut().insert(group_id, group);
+        });
+    }
+
+    Ok(())
+}
+
+// Promotes an active member to "moderator" or "admin".
+#[ic_cdk::update]
+fn promote_member(group_id: u64, user_id: Principal, role: String) -> Result<GroupMembership, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can promote members.".to_string());
+    }
+    if role != "moderator" && role != "admin" {
+        return Err("Members can only be promoted to moderator or admin.".to_string());
+    }
+    let membership = group_membership(group_id, user_id)
+        .filter(|m| m.status == "active")
+        .ok_or("This user is not a member of the group.".to_string())?;
+    if role_rank(&membership.role) <= role_rank(&role) {
+        return Err(format!("This member is already a {}.", membership.role));
+    }
+
+    let updated = GroupMembership { role, ..membership };
+    insert_membership(updated.clone());
+    log_group_activity(group_id, caller, "role_changed", None, Some(user_id), Some(updated.role.clone()));
+    Ok(updated)
+}
+
+// Demotes an admin or moderator back to a regular member.
+#[ic_cdk::update]
+fn demote_member(group_id: u64, user_id: Principal) -> Result<GroupMembership, String> {
+    let caller = ic_cdk::caller();
+    let membership = moderated_membership(group_id, caller, user_id)?;
+    if membership.role == "member" {
+        return Err("This user is already a regular member.".to_string());
+    }
+    ensure_other_admin(group_id, &membership)?;
+
+    let updated = GroupMembership { role: "member".to_string(), ..membership };
+    insert_membership(updated.clone());
+    log_group_activity(group_id, caller, "role_changed", None, Some(user_id), Some(updated.role.clone()));
+    Ok(updated)
+}
+
+// Only active members can be removed; banned users stay banned until
+// `unban_member` is called.
+#[ic_cdk::update]
+fn remove_member(group_id: u64, user_id: Principal) -> Result<GroupMembership, String> {
+    let caller = ic_cdk::caller();
+    let membership = moderated_membership(group_id, caller, user_id)?;
+    if membership.status != "active" {
+        return Err("This user is not a member of the group.".to_string());
+    }
+    ensure_other_admin(group_id, &membership)?;
+
+    let updated = GroupMembership {
+        role: "member".to_string(),
+        status: "inactive".to_string(),
+        ..membership
+    };
+    insert_membership(updated.clone());
+    log_group_activity(group_id, caller, "removed", None, Some(user_id), None);
+    Ok(updated)
+}
+
+// Banned users are removed from the group and cannot rejoin, request to join or
+// use invites until they are unbanned.
+#[ic_cdk::update]
+fn ban_member(group_id: u64, user_id: Principal) -> Result<GroupMembership, String> {
+    let caller = ic_cdk::caller();
+    let membership = match group_membership(group_id, user_id) {
+        Some(_) => moderated_membership(group_id, caller, user_id)?,
+        None => {
+            // Users who never joined can be banned pre-emptively.
+            if !is_group_admin(group_id, caller) {
+                return Err("Only group admins can manage members.".to_string());
+            }
+            GroupMembership {
+                id: next_id("group_membership"),
+                user_id,
+                group_id,
+                role: "member".to_string(),
+                status: "inactive".to_string(),
+                joined_at: ic_cdk::api::time(),
+                contributions: 0,
+                last_active_at: None,
+            }
+        }
+    };
+    if membership.status == "banned" {
+        return Err("This user is already banned.".to_string());
+    }
+    ensure_other_admin(group_id, &membership)?;
+
+    let updated = GroupMembership {
+        role: "member".to_string(),
+        status: "banned".to_string(),
+        ..membership
+    };
+    insert_membership(updated.clone());
+    log_group_activity(group_id, caller, "banned", None, Some(user_id), None);
+
+    if let Some(request_id) = PENDING_JOIN_REQUESTS.with(|pending| pending.borrow().get(&(group_id, user_id))) {
+        if let Some(request) = GROUP_JOIN_REQUESTS.with(|requests| requests.borrow().get(&request_id)) {
+            close_join_request(request, "rejected", Some(caller));
+        }
+    }
+
+    Ok(updated)
+}
+
+#[ic_cdk::update]
+fn unban_member(group_id: u64, user_id: Principal) -> Result<GroupMembership, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can manage members.".to_string());
+    }
+    let membership = group_membership(group_id, user_id)
+        .filter(|m| m.status == "banned")
+        .ok_or("This user is not banned from the group.".to_string())?;
+
+    let updated = GroupMembership { status: "inactive".to_string(), ..membership };
+    insert_membership(updated.clone());
+    log_group_activity(group_id, caller, "unbanned", None, Some(user_id), None);
+    Ok(updated)
+}
+
+#[ic_cdk::query]
+fn get_banned_members(group_id: u64) -> Result<Vec<GroupMembership>, String> {
+    let caller = ic_cdk::caller();
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can view banned members.".to_string());
+    }
+    Ok(group_memberships(group_id).into_iter().filter(|m| m.status == "banned").collect())
+}
+
+// --- Group Activity Methods ---
+
+// Newest-first page of the group's activity log. Pass the smallest id received
+// as `before_cursor` to load older entries.
+#[ic_cdk::query]
+fn get_group_activity(group_id: u64, before_cursor: Option<u64>, limit: u64) -> Result<Vec<GroupActivity>, String> {
+    let caller = ic_cdk::caller();
+    active_membership(group_id, caller)?;
+    let before = before_cursor.unwrap_or(u64::MAX);
+
+    Ok(GROUP_ACTIVITIES.with(|activities| {
+        activities
+            .borrow()
+            .range((group_id, 0)..(group_id, before))
+            .rev()
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(_, activity)| activity)
+            .collect()
+    }))
+}
+
+#[ic_cdk::query]
+fn get_group_activity_stats(group_id: u64) -> Result<GroupActivityStats, String> {
+    let caller = ic_cdk::caller();
+    viewable_group(group_id, caller)?;
+
+    let today = day_index(ic_cdk::api::time());
+    let first_day = today + 1 - ACTIVITY_STATS_DAYS;
+    let active_since = |days: u64| -> u32 {
+        let mut members: Vec<Principal> = GROUP_ACTIVE_MEMBERS.with(|index| {
+            index
+                .borrow()
+                .range((group_id, today + 1 - days, Principal::management_canister())..)
+                .take_while(|((group, _, _), _)| *group == group_id)
+                .map(|((_, _, user), _)| user)
+                .collect()
+        });
+        members.sort();
+        members.dedup();
+        members.len() as u32
+    };
+
+    let daily_messages: Vec<DailyCount> = (first_day..=today)
+        .map(|day| DailyCount {
+            date: day * NANOS_PER_DAY,
+            count: GROUP_DAILY_MESSAGES.with(|counts| counts.borrow().get(&(group_id, day))).unwrap_or(0),
+        })
+        .collect();
+    let messages_in = |days: usize| -> f32 {
+        let total: u32 = daily_messages.iter().rev().take(days).map(|d| d.count).sum();
+        total as f32 / days as f32
+    };
+
+    Ok(GroupActivityStats {
+        group_id,
+        active_members_7d: active_since(7),
+        active_members_30d: active_since(ACTIVITY_STATS_DAYS),
+        messages_per_day_7d: messages_in(7),
+        messages_per_day_30d: messages_in(ACTIVITY_STATS_DAYS as usize),
+        daily_messages,
+    })
+}
+
+// --- Group Chat Methods ---
+
+// Posts a message to a group's chat. Replies always attach to the root of a
+// thread, so threads are one level deep.
+#[ic_cdk::update]
+fn post_group_message(
+    group_id: u64,
+    content: String,
+    attachments: Option<Vec<String>>,
+    reply_to: Option<u64>,
+) -> Result<GroupMessage, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    let membership = active_membership(group_id, caller)?;
+    let content = validate_message_content(content)?;
+    if attachments.as_ref().is_some_and(|a| a.len() > MAX_MESSAGE_ATTACHMENTS) {
+        return Err(format!("A message can have at most {} attachments.", MAX_MESSAGE_ATTACHMENTS));
+    }
+
+    let root = match reply_to {
+        Some(parent_id) => {
+            let parent = GROUP_MESSAGES.with(|messages| messages.borrow().get(&(group_id, parent_id)))
+                .ok_or("The message you are replying to was not found.".to_string())?;
+            let root_id = parent.reply_to.unwrap_or(parent.id);
+            let root = GROUP_MESSAGES.with(|messages| messages.borrow().get(&(group_id, root_id)))
+                .ok_or("The message you are replying to was not found.".to_string())?;
+            if root.is_deleted {
+                return Err("You cannot reply to a deleted message.".to_string());
+            }
+            Some(root)
+        }
+        None => None,
+    };
+
+    let now = ic_cdk::api::time();
+    let message = GroupMessage {
+        id: last_group_message_id(group_id) + 1,
+        group_id,
+        user_id: caller,
+        content,
+        timestamp: now,
+        attachments,
+        reply_to: root.as_ref().map(|r| r.id),
+        reply_count: 0,
+        edited_at: None,
+        is_deleted: false,
+        deleted_by: None,
+    };
+
+    GROUP_MESSAGES.with(|messages| {
+        let mut messages = messages.borrow_mut();
+        messages.insert((group_id, message.id), message.clone());
+        if let Some(mut root) = root {
+            root.reply_count += 1;
+            messages.insert((group_id, root.id), root);
+        }
+    });
+    if let Some(root_id) = message.reply_to {
+        GROUP_MESSAGE_REPLIES.with(|replies| {
+            replies.borrow_mut().insert((group_id, root_id, message.id), ());
+        });
+    }
+
+    insert_membership(GroupMembership { last_active_at: Some(now), ..membership });
+    log_group_activity(group_id, caller, "message", Some(message.id), None, None);
+    refresh_group_stats(group_id, Some(now));
+
+    Ok(message)
+}
+
+// Newest-first page of a group's chat, including replies. Pass the smallest id
+// received as `before_cursor` to load older messages.
+#[ic_cdk::query]
+fn get_group_messages(group_id: u64, before_cursor: Option<u64>, limit: u64) -> Result<Vec<GroupMessage>, String> {
+    let caller = ic_cdk::caller();
+    active_membership(group_id, caller)?;
+    let before = before_cursor.unwrap_or(u64::MAX);
+
+    Ok(GROUP_MESSAGES.with(|messages| {
+        messages
+            .borrow()
+            .range((group_id, 0)..(group_id, before))
+            .rev()
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(_, message)| message)
+            .collect()
+    }))
+}
+
+// Oldest-first messages posted after `since_id`. Clients poll this with the
+// highest id they have seen to keep a chat view up to date.
+#[ic_cdk::query]
+fn get_messages_since(group_id: u64, since_id: u64, limit: u64) -> Result<Vec<GroupMessage>, String> {
+    let caller = ic_cdk::caller();
+    active_membership(group_id, caller)?;
+
+    Ok(GROUP_MESSAGES.with(|messages| {
+        messages
+            .borrow()
+            .range((group_id, since_id.saturating_add(1))..=(group_id, u64::MAX))
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(_, message)| message)
+            .collect()
+    }))
+}
+
+// Oldest-first replies in a thread.
+#[ic_cdk::query]
+fn get_message_replies(group_id: u64, message_id: u64, limit: u64) -> Result<Vec<GroupMessage>, String> {
+    let caller = ic_cdk::caller();
+    active_membership(group_id, caller)?;
+
+    let reply_ids: Vec<u64> = GROUP_MESSAGE_REPLIES.with(|replies| {
+        replies
+            .borrow()
+            .range((group_id, message_id, 0)..=(group_id, message_id, u64::MAX))
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|((_, _, id), _)| id)
+            .collect()
+    });
+    Ok(GROUP_MESSAGES.with(|messages| {
+        let messages = messages.borrow();
+        reply_ids.iter().filter_map(|id| messages.get(&(group_id, *id))).collect()
+    }))
+}
+
+#[ic_cdk::update]
+fn edit_group_message(group_id: u64, message_id: u64, content: String) -> Result<GroupMessage, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    active_membership(group_id, caller)?;
+    let content = validate_message_content(content)?;
+
+    let mut message = GROUP_MESSAGES.with(|messages| messages.borrow().get(&(group_id, message_id)))
+        .ok_or("Message not found.".to_string())?;
+    if message.user_id != caller {
+        return Err("You can only edit your own messages.".to_string());
+    }
+    if message.is_deleted {
+        return Err("This message has been deleted.".to_string());
+    }
+
+    message.content = content;
+    message.edited_at = Some(ic_cdk::api::time());
+    GROUP_MESSAGES.with(|messages| {
+        messages.borrow_mut().insert((group_id, message_id), message.clone());
+    });
+    log_group_activity(group_id, caller, "message_edited", Some(message_id), None, None);
+
+    Ok(message)
+}
+
+// Authors can delete their own messages; admins and moderators can delete any.
+#[ic_cdk::update]
+fn delete_group_message(group_id: u64, message_id: u64) -> Result<GroupMessage, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    let membership = active_membership(group_id, caller)?;
+
+    let mut message = GROUP_MESSAGES.with(|messages| messages.borrow().get(&(group_id, message_id)))
+        .ok_or("Message not found.".to_string())?;
+    if message.user_id != caller && role_rank(&membership.role) > role_rank("moderator") {
+        return Err("Only the author or a group moderator can delete this message.".to_string());
+    }
+    if message.is_deleted {
+        return Err("This message has already been deleted.".to_string());
+    }
+
+    message.content = String::new();
+    message.attachments = None;
+    message.is_deleted = true;
+    message.deleted_by = Some(caller);
+    GROUP_MESSAGES.with(|messages| {
+        messages.borrow_mut().insert((group_id, message_id), message.clone());
+    });
+    log_group_activity(group_id, caller, "message_deleted", Some(message_id), Some(message.user_id), None);
+
+    Ok(message)
+}
+
+// --- Study Resource Methods ---
+
+#[ic_cdk::update]
+fn add_study_resource(group_id: u64, resource: NewStudyResource) -> Result<StudyResource, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    let membership = active_membership(group_id, caller)?;
+
+    let title = resource.title.trim().to_string();
+    if title.is_empty() {
+        return Err("Resource title cannot be empty.".to_string());
+    }
+    let tags = normalize_tags(resource.tags)?;
+    let resource_url = resource.resource_url.and_then(non_empty);
+    let content = resource.content.and_then(non_empty);
+
+    let mut file: Option<ResourceFile> = None;
+    match resource.resource_type.as_str() {
+        "link" => {
+            if resource_url.is_none() {
+                return Err("Link resources need a URL.".to_string());
+            }
+        }
+        "note" => {
+            if content.is_none() {
+                return Err("Note resources need content.".to_string());
+            }
+        }
+        "file" => {
+            let upload = resource.file.ok_or("File resources need a file.".to_string())?;
+            let size = upload.data.len() as u64;
+            if size == 0 {
+                return Err("The uploaded file is empty.".to_string());
+            }
+            if size > MAX_RESOURCE_FILE_BYTES {
+                return Err(format!("Files cannot exceed {} bytes.", MAX_RESOURCE_FILE_BYTES));
+            }
+            if group_file_bytes(group_id) + size > MAX_GROUP_FILE_BYTES {
+                return Err("This group has run out of file storage.".to_string());
+            }
+            file = Some(upload);
+        }
+        _ => return Err("Resource type must be one of: link, file, note.".to_string()),
+    }
+
+    let now = ic_cdk::api::time();
+    let resource_id = next_id("study_resource");
+    let new_resource = StudyResource {
+        id: resource_id,
+        group_id,
+        user_id: caller,
+        title,
+        description: resource.description.and_then(non_empty),
+        resource_type: resource.resource_type,
+        resource_url,
+        content,
+        created_at: now,
+        updated_at: now,
+        tags,
+        file_name: file.as_ref().map(|f| f.name.clone()),
+        mime_type: file.as_ref().map(|f| f.mime_type.clone()),
+        file_size: file.as_ref().map(|f| f.data.len() as u64),
+        view_count: 0,
+        download_count: 0,
+    };
+
+    if let Some(file) = file {
+        add_group_file_bytes(group_id, file.data.len() as i64);
+        RESOURCE_FILES.with(|files| {
+            files.borrow_mut().insert(resource_id, file.data);
+        });
+    }
+    STUDY_RESOURCES.with(|resources| {
+        resources.borrow_mut().insert(resource_id, new_resource.clone());
+    });
+    RESOURCES_BY_GROUP.with(|index| {
+        index.borrow_mut().insert((group_id, resource_id), ());
+    });
+    update_resource_tags(&new_resource, &[]);
+
+    insert_membership(GroupMembership {
+        contributions: membership.contributions + 1,
+        last_active_at: Some(now),
+        ..membership
+    });
+    log_group_activity(group_id, caller, "resource", Some(resource_id), None, Some(new_resource.title.clone()));
+    record_event(caller, "resource_shared", resource_id, Some(group_id), new_resource.title.clone());
+
+    Ok(new_resource)
+}
+
+// Authors can edit their own resources; admins and moderators can edit any.
+#[ic_cdk::update]
+fn update_study_resource(resource_id: u64, update: StudyResourceUpdate) -> Result<StudyResource, String> {
+    let caller = ic_cdk::caller();
+    let mut resource = managed_resource(resource_id, caller)?;
+    let old_tags = resource.tags.clone();
+
+    if let Some(title) = update.title {
+        let title = title.trim().to_string();
+        if title.is_empty() {
+            return Err("Resource title cannot be empty.".to_string());
+        }
+        resource.title = title;
+    }
+    if let Some(description) = update.description {
+        resource.description = non_empty(description);
+    }
+    if let Some(url) = update.resource_url {
+        if resource.resource_type == "link" && non_empty(url.clone()).is_none() {
+            return Err("Link resources need a URL.".to_string());
+        }
+        resource.resource_url = non_empty(url);
+    }
+    if let Some(content) = update.content {
+        if resource.resource_type == "note" && non_empty(content.clone()).is_none() {
+            return Err("Note resources need content.".to_string());
+        }
+        resource.content = non_empty(content);
+    }
+    if let Some(tags) = update.tags {
+        resource.tags = normalize_tags(tags)?;
+    }
+
+    resource.updated_at = ic_cdk::api::time();
+    STUDY_RESOURCES.with(|resources| {
+        resources.borrow_mut().insert(resource_id, resource.clone());
+    });
+    update_resource_tags(&resource, &old_tags);
+    log_group_activity(resource.group_id, caller, "resource_updated", Some(resource_id), None, Some(resource.title.clone()));
+
+    Ok(resource)
+}
+
+#[ic_cdk::update]
+fn remove_study_resource(resource_id: u64) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    let resource = managed_resource(resource_id, caller)?;
+    delete_resource(&resource);
+    log_group_activity(resource.group_id, caller, "resource_removed", Some(resource_id), None, Some(resource.title));
+    Ok(())
+}
+
+// Lists a group's resources, newest first. `tag` filters by exact tag and
+// `query` matches words in the title, description and tags.
+#[ic_cdk::query]
+fn get_group_resources(
+    group_id: u64,
+    tag: Option<String>,
+    query: Option<String>,
+    offset: u64,
+    limit: u64,
+) -> Result<Vec<StudyResource>, String> {
+    let caller = ic_cdk::caller();
+    viewable_group(group_id, caller)?;
+
+    let query_words = keywords(query.as_deref().unwrap_or(""));
+    let page = |ids: &mut dyn Iterator<Item = u64>| -> Vec<StudyResource> {
+        STUDY_RESOURCES.with(|resources| {
+            let resources = resources.borrow();
+            ids.filter_map(|id| resources.get(&id))
+                .filter(|resource| {
+                    let words = keywords(&format!(
+                        "{} {} {}",
+                        resource.title,
+                        resource.description.as_deref().unwrap_or(""),
+                        resource.tags.join(" "),
+                    ));
+                    query_words.iter().all(|q| words.iter().any(|w| w.starts_with(q.as_str())))
+                })
+                .skip(offset as usize)
+                .take(limit.min(MAX_PAGE_SIZE) as usize)
+                .collect()
+        })
+    };
+
+    Ok(match tag.as_deref().and_then(normalize_topic) {
+        Some(tag) => RESOURCE_TAGS.with(|index| {
+            let tag = IndexTerm::new(&tag);
+            let index = index.borrow();
+            let mut ids = index
+                .range((group_id, tag.clone(), 0)..=(group_id, tag, u64::MAX))
+                .rev()
+                .map(|((_, _, id), _)| id);
+            page(&mut ids)
+        }),
+        None => RESOURCES_BY_GROUP.with(|index| {
+            let index = index.borrow();
+            let mut ids = index.range((group_id, 0)..=(group_id, u64::MAX)).rev().map(|((_, id), _)| id);
+            page(&mut ids)
+        }),
+    })
+}
+
+// Opens a resource and counts the view.
+#[ic_cdk::update]
+fn view_study_resource(resource_id: u64) -> Result<StudyResource, String> {
+    let caller = ic_cdk::caller();
+    let mut resource = STUDY_RESOURCES.with(|resources| resources.borrow().get(&resource_id))
+        .ok_or("Resource not found.".to_string())?;
+    viewable_group(resource.group_id, caller)?;
+
+    resource.view_count += 1;
+    STUDY_RESOURCES.with(|resources| {
+        resources.borrow_mut().insert(resource_id, resource.clone());
+    });
+
+    Ok(resource)
+}
+
+// Returns an uploaded file's contents and counts the download.
+#[ic_cdk::update]
+fn download_resource_file(resource_id: u64) -> Result<ResourceFile, String> {
+    let caller = ic_cdk::caller();
+    let mut resource = STUDY_RESOURCES.with(|resources| resources.borrow().get(&resource_id))
+        .ok_or("Resource not found.".to_string())?;
+    viewable_group(resource.group_id, caller)?;
+    let data = RESOURCE_FILES.with(|files| files.borrow().get(&resource_id))
+        .ok_or("This resource has no file.".to_string())?;
+
+    resource.download_count += 1;
+    STUDY_RESOURCES.with(|resources| {
+        resources.borrow_mut().insert(resource_id, resource.clone());
+    });
+
+    Ok(ResourceFile {
+        name: resource.file_name.unwrap_or_default(),
+        mime_type: resource.mime_type.unwrap_or_default(),
+        data,
+    })
+}
+
+// --- Group Poll Methods ---
+
+#[ic_cdk::update]
+fn create_poll(group_id: u64, poll: NewPoll) -> Result<GroupPoll, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    active_membership(group_id, caller)?;
+
+    let question = poll.question.trim().to_string();
+    if question.is_empty() {
+        return Err("Poll question cannot be empty.".to_string());
+    }
+    let options: Vec<String> = poll.options.into_iter().filter_map(non_empty).collect();
+    if options.len() < 2 || options.len() > MAX_POLL_OPTIONS {
+        return Err(format!("A poll needs between 2 and {} options.", MAX_POLL_OPTIONS));
+    }
+    let now = ic_cdk::api::time();
+    if poll.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err("Poll expiry must be in the future.".to_string());
+    }
+
+    let poll_id = next_id("poll");
+    let new_poll = GroupPoll {
+        id: poll_id,
+        group_id,
+        creator_id: caller,
+        question,
+        created_at: now,
+        expires_at: poll.expires_at,
+        is_active: true,
+        allow_multiple: poll.allow_multiple,
+        is_anonymous: poll.is_anonymous,
+        hide_results_until_closed: poll.hide_results_until_closed,
+        closed_at: None,
+    };
+
+    POLL_OPTIONS.with(|store| {
+        let mut store = store.borrow_mut();
+        for (index, text) in options.into_iter().enumerate() {
+            let option_id = index as u64 + 1;
+            store.insert((poll_id, option_id), PollOption { id: option_id, poll_id, text });
+        }
+    });
+    GROUP_POLLS.with(|polls| {
+        polls.borrow_mut().insert(poll_id, new_poll.clone());
+    });
+    POLLS_BY_GROUP.with(|index| {
+        index.borrow_mut().insert((group_id, poll_id), ());
+    });
+    schedule_poll_close(&new_poll);
+    log_group_activity(group_id, caller, "poll", Some(poll_id), None, Some(new_poll.question.clone()));
+    refresh_group_stats(group_id, Some(now));
+
+    Ok(new_poll)
+}
+
+// Records the caller's vote, replacing any earlier vote on the same poll.
+// Single-choice polls take exactly one option.
+#[ic_cdk::update]
+fn vote_poll(poll_id: u64, option_ids: Vec<u64>) -> Result<PollResults, String> {
+    let caller = ic_cdk::caller();
+    let poll = open_poll(poll_id)?;
+    writable_group(poll.group_id)?;
+    active_membership(poll.group_id, caller)?;
+
+    let mut option_ids = option_ids;
+    option_ids.sort();
+    option_ids.dedup();
+    if option_ids.is_empty() {
+        return Err("Choose at least one option.".to_string());
+    }
+    if !poll.allow_multiple && option_ids.len() > 1 {
+        return Err("This poll allows only one choice.".to_string());
+    }
+    if option_ids
+        .iter()
+        .any(|id| !POLL_OPTIONS.with(|options| options.borrow().contains_key(&(poll_id, *id))))
+    {
+        return Err("Option not found in this poll.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    POLL_VOTES.with(|votes| {
+        let mut votes = votes.borrow_mut();
+        for key in user_poll_votes(&votes, poll_id, caller) {
+            votes.remove(&key);
+        }
+        for option_id in option_ids {
+            let vote = PollVote {
+                id: next_id("poll_vote"),
+                poll_id,
+                option_id,
+                user_id: caller,
+                timestamp: now,
+            };
+            votes.insert((poll_id, caller, option_id), vote);
+        }
+    });
+
+    Ok(poll_results(poll, caller))
+}
+
+// Closes a poll early. Allowed for its creator and the group's admins and moderators.
+#[ic_cdk::update]
+fn close_poll(poll_id: u64) -> Result<GroupPoll, String> {
+    let caller = ic_cdk::caller();
+    let poll = open_poll(poll_id)?;
+    let membership = active_membership(poll.group_id, caller)?;
+    if poll.creator_id != caller && role_rank(&membership.role) > role_rank("moderator") {
+        return Err("Only the poll creator or a group moderator can close this poll.".to_string());
+    }
+    log_group_activity(poll.group_id, caller, "poll_closed", Some(poll.id), None, None);
+    Ok(finish_poll(poll))
+}
+
+#[ic_cdk::query]
+fn get_group_polls(group_id: u64) -> Result<Vec<GroupPoll>, String> {
+    let caller = ic_cdk::caller();
+    viewable_group(group_id, caller)?;
+    let poll_ids: Vec<u64> = POLLS_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, 0)..=(group_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    Ok(GROUP_POLLS.with(|polls| {
+        let polls = polls.borrow();
+        poll_ids.iter().rev().filter_map(|id| polls.get(id)).collect()
+    }))
+}
+
+#[ic_cdk::query]
+fn get_poll_results(poll_id: u64) -> Result<PollResults, String> {
+    let caller = ic_cdk::caller();
+    let poll = GROUP_POLLS.with(|polls| polls.borrow().get(&poll_id))
+        .ok_or("Poll not found.".to_string())?;
+    viewable_group(poll.group_id, caller)?;
+    Ok(poll_results(poll, caller))
+}
+
+// --- Study Session Methods ---
+
+// Schedules a session. Group admins and moderators can schedule; the organizer
+// is confirmed automatically.
+#[ic_cdk::update]
+fn schedule_session(group_id: u64, session: NewStudySession) -> Result<StudySession, String> {
+    let caller = ic_cdk::caller();
+    writable_group(group_id)?;
+    let membership = active_membership(group_id, caller)?;
+    if role_rank(&membership.role) > role_rank("moderator") {
+        return Err("Only group admins and moderators can schedule sessions.".to_string());
+    }
+    let session = validate_new_session(session)?;
+
+    let now = ic_cdk::api::time();
+    let session_id = next_id("study_session");
+    let new_session = StudySession {
+        id: session_id,
+        group_id,
+        creator_id: caller,
+        title: session.title,
+        description: session.description,
+        starts_at: session.starts_at,
+        timezone: session.timezone,
+        utc_offset_minutes: session.utc_offset_minutes,
+        duration_minutes: session.duration_minutes,
+        max_participants: session.max_participants,
+        topics: session.topics,
+        status: "scheduled".to_string(),
+        confirmed_count: 0,
+        created_at: now,
+        updated_at: now,
+        series_id: None,
+        occurrence_start: None,
+    };
+    store_session(&new_session);
+    let new_session = set_rsvp(new_session, caller, "confirmed")?;
+    log_group_activity(group_id, caller, "session", Some(session_id), None, Some(new_session.title.clone()));
+    refresh_group_stats(group_id, Some(now));
+
+    Ok(new_session)
+}
+
+// Responds to a session. Asking for "confirmed" when the session is full puts
+// the caller on the waitlist; seats freed by declines go to the waitlist in order.
+#[ic_cdk::update]
+fn rsvp_session(session_id: u64, status: String) -> Result<SessionParticipant, String> {
+    let caller = ic_cdk::caller();
+    if !["confirmed", "pending", "declined"].contains(&status.as_str()) {
+        return Err("RSVP must be one of: confirmed, pending, declined.".to_string());
+    }
+    let session = STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&session_id))
+        .ok_or("Session not found.".to_string())?;
+    writable_group(session.group_id)?;
+    active_membership(session.group_id, caller)?;
+    if session.status != "scheduled" {
+        return Err("This session has been cancelled.".to_string());
+    }
+    if session.starts_at <= ic_cdk::api::time() {
+        return Err("This session has already started.".to_string());
+    }
+
+    set_rsvp(session, caller, &status)?;
+    SESSION_PARTICIPANTS.with(|participants| participants.borrow().get(&(session_id, caller)))
+        .ok_or("RSVP not found.".to_string())
+}
+
+// Cancels a session. Allowed for its organizer and the group's admins and moderators.
+#[ic_cdk::update]
+fn cancel_session(session_id: u64) -> Result<StudySession, String> {
+    let caller = ic_cdk::caller();
+    let mut session = managed_session(session_id, caller)?;
+    if session.status == "cancelled" {
+        return Err("This session has already been cancelled.".to_string());
+    }
+
+    session.status = "cancelled".to_string();
+    session.updated_at = ic_cdk::api::time();
+    STUDY_SESSIONS.with(|sessions| {
+        sessions.borrow_mut().insert(session_id, session.clone());
+    });
+    log_group_activity(session.group_id, caller, "session_cancelled", Some(session_id), None, Some(session.title.clone()));
+
+    Ok(session)
+}
+
+// Confirms the caller's attendance during the check-in window. Members without
+// a confirmed RSVP can check in as walk-ins.
+#[ic_cdk::update]
+fn check_in_session(session_id: u64) -> Result<SessionAttendance, String> {
+    let caller = ic_cdk::caller();
+    let session = STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&session_id))
+        .ok_or("Session not found.".to_string())?;
+    writable_group(session.group_id)?;
+    active_membership(session.group_id, caller)?;
+    if session.status != "scheduled" {
+        return Err("This session has been cancelled.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    if now < session.starts_at.saturating_sub(CHECK_IN_OPENS_BEFORE) {
+        return Err(format!(
+            "Check-in opens {} minutes before the session starts.",
+            CHECK_IN_OPENS_BEFORE / NANOS_PER_MINUTE
+        ));
+    }
+    if now >= session_end(&session) {
+        return Err("Check-in for this session has closed.".to_string());
+    }
+
+    record_attendance(&session, caller, caller)
+}
+
+// Lets the organizer or a group moderator mark a member as attended or not,
+// from the start of the session until shortly after it ends.
+#[ic_cdk::update]
+fn set_session_attendance(session_id: u64, user_id: Principal, attended: bool) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    let session = managed_session(session_id, caller)?;
+    if session.status != "scheduled" {
+        return Err("This session has been cancelled.".to_string());
+    }
+    let now = ic_cdk::api::time();
+    if now < session.starts_at {
+        return Err("This session has not started yet.".to_string());
+    }
+    if now >= session_end(&session) + ATTENDANCE_CORRECTION_WINDOW {
+        return Err("Attendance for this session can no longer be changed.".to_string());
+    }
+
+    if attended {
+        active_membership(session.group_id, user_id)
+            .map_err(|_| "This user is not a member of the group.".to_string())?;
+        record_attendance(&session, user_id, caller)?;
+        return Ok(());
+    }
+
+    if SESSION_ATTENDANCE.with(|attendance| attendance.borrow_mut().remove(&(session_id, user_id))).is_none() {
+        return Err("This user has not checked in.".to_string());
+    }
+    if SESSION_PARTICIPANTS.with(|participants| !participants.borrow().contains_key(&(session_id, user_id))) {
+        SESSIONS_BY_USER.with(|index| index.borrow_mut().remove(&(user_id, session.starts_at, session_id)));
+    }
+    // The contribution goes away with the check-in; last_active_at is left alone.
+    if let Some(membership) = group_membership(session.group_id, user_id) {
+        insert_membership(GroupMembership {
+            contributions: membership.contributions.saturating_sub(1),
+            ..membership
+        });
+    }
+    Ok(())
+}
+
+#[ic_cdk::query]
+fn get_session_attendance(session_id: u64) -> Result<Vec<SessionAttendance>, String> {
+    let caller = ic_cdk::caller();
+    let session = STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&session_id))
+        .ok_or("Session not found.".to_string())?;
+    viewable_group(session.group_id, caller)?;
+
+    let mut attendance = session_attendance(session_id);
+    attendance.sort_by_key(|a| a.checked_in_at);
+    Ok(attendance)
+}
+
+// Attendance rates for the group as a whole and for each current member,
+// over sessions that have ended.
+#[ic_cdk::query]
+fn get_group_attendance(group_id: u64) -> Result<GroupAttendanceReport, String> {
+    let caller = ic_cdk::caller();
+    viewable_group(group_id, caller)?;
+    Ok(group_attendance(group_id))
+}
+
+#[ic_cdk::query]
+fn get_member_attendance(group_id: u64, user_id: Principal) -> Result<AttendanceStats, String> {
+    let caller = ic_cdk::caller();
+    viewable_group(group_id, caller)?;
+    active_membership(group_id, user_id).map_err(|_| "This user is not a member of the group.".to_string())?;
+    Ok(member_attendance(group_id, user_id))
+}
+
+// Schedules a recurring session. The group's `meeting_frequency` is updated to
+// describe the rule.
+#[ic_cdk::update]
+fn schedule_recurring_session(
+    group_id: u64,
+    session: NewStudySession,
+    rule: RecurrenceRule,
+    until: Option<u64>,
+) -> Result<SessionSeries, String> {
+    let caller = ic_cdk::caller();
+    let mut group = writable_group(group_id)?;
+    let membership = active_membership(group_id, caller)?;
+    if role_rank(&membership.role) > role_rank("moderator") {
+        return Err("Only group admins and moderators can schedule sessions.".to_string());
+    }
+    let session = validate_new_session(session)?;
+    let rule = validate_recurrence_rule(rule, session.starts_at, session.utc_offset_minutes)?;
+    if until.is_some_and(|until| until < session.starts_at) {
+        return Err("The series must end after its first session.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    let mut series = SessionSeries {
+        id: next_id("session_series"),
+        group_id,
+        creator_id: caller,
+        title: session.title,
+        description: session.description,
+        first_start: session.starts_at,
+        until,
+        timezone: session.timezone,
+        utc_offset_minutes: session.utc_offset_minutes,
+        duration_minutes: session.duration_minutes,
+        max_participants: session.max_participants,
+        topics: session.topics,
+        rule,
+        generated_until: session.starts_at - 1,
+        status: "active".to_string(),
+        created_at: now,
+        updated_at: now,
+    };
+    generate_series_sessions(&mut series, now + SERIES_WINDOW);
+
+    group.meeting_frequency = Some(describe_recurrence(&series.rule));
+    group.updated_at = now;
+    STUDY_GROUPS.with(|groups| {
+        groups.borrow_mut().insert(group_id, group);
+    });
+    log_group_activity(group_id, caller, "session_series", Some(series.id), None, Some(series.title.clone()));
+    refresh_group_stats(group_id, Some(now));
+
+    Ok(series)
+}
+
+// Edits a session. For an occurrence of a recurring series, `scope` "this"
+// changes only that occurrence, while "future" changes it and every later
+// occurrence. Moving the time of future occurrences splits the series in two.
+#[ic_cdk::update]
+fn update_session(session_id: u64, update: StudySessionUpdate, scope: String) -> Result<Vec<StudySession>, String> {
+    let caller = ic_cdk::caller();
+    let session = managed_session(session_id, caller)?;
+    if session.status == "cancelled" {
+        return Err("This session has been cancelled.".to_string());
+    }
+    let update = validate_session_update(update)?;
+    let group_id = session.group_id;
+
+    let updated = match scope.as_str() {
+        "this" => vec![apply_session_update(session, &update, None)?],
+        "future" => {
+            let series_id = session.series_id.ok_or("This session is not part of a series.".to_string())?;
+            let mut series = SESSION_SERIES.with(|series| series.borrow().get(&series_id))
+                .ok_or("Series not found.".to_string())?;
+            let occurrence_start = session.occurrence_start.unwrap_or(session.starts_at);
+            let delta = update.starts_at.map(|start| start as i64 - session.starts_at as i64);
+
+            let future: Vec<StudySession> = series_session_ids(series_id, occurrence_start)
+                .into_iter()
+                .filter_map(|id| STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&id)))
+                .filter(|s| s.status == "scheduled")
+                .collect();
+            if let Some(max_participants) = update.max_participants {
+                if future.iter().any(|s| s.confirmed_count > max_participants) {
+                    return Err("max_participants cannot be below the number of confirmed participants of any affected session.".to_string());
+                }
+            }
+
+            // Update the template so occurrences generated later pick up the changes.
+            if let Some(title) = &update.title {
+                series.title = title.clone();
+            }
+            if let Some(description) = &update.description {
+                series.description = non_empty(description.clone());
+            }
+            if let Some(duration) = update.duration_minutes {
+                series.duration_minutes = duration;
+            }
+            if let Some(max_participants) = update.max_participants {
+                series.max_participants = max_participants;
+            }
+            if let Some(topics) = &update.topics {
+                series.topics = topics.clone();
+            }
+            series.updated_at = ic_cdk::api::time();
+
+            let target_series = match delta.filter(|delta| *delta != 0) {
+                Some(delta) => split_series(&mut series, occurrence_start, delta),
+                None => {
+                    SESSION_SERIES.with(|store| {
+                        store.borrow_mut().insert(series_id, series.clone());
+                    });
+                    series
+                }
+            };
+
+            future
+                .into_iter()
+                .map(|s| {
+                    let move_by = delta.map(|delta| (target_series.id, delta));
+                    let update = StudySessionUpdate { starts_at: None, ..update.clone() };
+                    apply_session_update(s, &update, move_by)
+                })
+                .collect::<Result<Vec<StudySession>, String>>()?
+        }
+        _ => return Err("Scope must be \"this\" or \"future\".".to_string()),
+    };
+
+    let title = updated.first().map(|s| s.title.clone());
+    log_group_activity(group_id, caller, "session_updated", Some(session_id), None, title);
+    Ok(updated)
+}
+
+// Stops a recurring series and cancels its occurrences that have not started yet.
+#[ic_cdk::update]
+fn end_session_series(series_id: u64) -> Result<SessionSeries, String> {
+    let caller = ic_cdk::caller();
+    let mut series = SESSION_SERIES.with(|series| series.borrow().get(&series_id))
+        .ok_or("Series not found.".to_string())?;
+    writable_group(series.group_id)?;
+    let membership = active_membership(series.group_id, caller)?;
+    if series.creator_id != caller && role_rank(&membership.role) > role_rank("moderator") {
+        return Err("Only the organizer or a group moderator can end this series.".to_string());
+    }
+    if series.status == "ended" {
+        return Err("This series has already ended.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    for session_id in series_session_ids(series_id, 0) {
+        if let Some(mut session) = STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&session_id)) {
+            if session.status == "scheduled" && session.starts_at > now {
+                session.status = "cancelled".to_string();
+                session.updated_at = now;
+                STUDY_SESSIONS.with(|sessions| {
+                    sessions.borrow_mut().insert(session_id, session);
+                });
+            }
+        }
+    }
+
+    series.status = "ended".to_string();
+    series.until = Some(now);
+    series.updated_at = now;
+    SESSION_SERIES.with(|store| {
+        store.borrow_mut().insert(series_id, series.clone());
+    });
+    log_group_activity(series.group_id, caller, "session_series_ended", Some(series_id), None, Some(series.title.clone()));
+
+    Ok(series)
+}
+
+#[ic_cdk::query]
+fn get_session_series(series_id: u64) -> Result<SessionSeries, String> {
+    let caller = ic_cdk::caller();
+    let series = SESSION_SERIES.with(|series| series.borrow().get(&series_id))
+        .ok_or("Series not found.".to_string())?;
+    viewable_group(series.group_id, caller)?;
+    Ok(series)
+}
+
+// Scheduled sessions of a group that have not ended yet, soonest first.
+#[ic_cdk::query]
+fn get_upcoming_sessions(group_id: u64, limit: u64) -> Result<Vec<StudySession>, String> {
+    let caller = ic_cdk::caller();
+    viewable_group(group_id, caller)?;
+    // Sessions last at most a day, so anything that started earlier has ended.
+    let from = ic_cdk::api::time().saturating_sub(MAX_SESSION_MINUTES as u64 * NANOS_PER_MINUTE);
+    let now = ic_cdk::api::time();
+
+    let session_ids: Vec<u64> = SESSIONS_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, from, 0)..=(group_id, u64::MAX, u64::MAX))
+            .map(|((_, _, id), _)| id)
+            .collect()
+    });
+    Ok(STUDY_SESSIONS.with(|sessions| {
+        let sessions = sessions.borrow();
+        session_ids
+            .iter()
+            .filter_map(|id| sessions.get(id))
+            .filter(|s| s.status == "scheduled" && session_end(s) > now)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .collect()
+    }))
+}
+
+// Upcoming sessions the caller has not declined, soonest first.
+#[ic_cdk::query]
+fn get_my_upcoming_sessions(limit: u64) -> Vec<UserSession> {
+    let caller = ic_cdk::caller();
+    let from = ic_cdk::api::time().saturating_sub(MAX_SESSION_MINUTES as u64 * NANOS_PER_MINUTE);
+    let now = ic_cdk::api::time();
+
+    let session_ids: Vec<u64> = SESSIONS_BY_USER.with(|index| {
+        index
+            .borrow()
+            .range((caller, from, 0)..=(caller, u64::MAX, u64::MAX))
+            .map(|((_, _, id), _)| id)
+            .collect()
+    });
+    session_ids
+        .into_iter()
+        .filter_map(|id| {
+            let session = STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&id))?;
+            let rsvp = SESSION_PARTICIPANTS.with(|participants| participants.borrow().get(&(id, caller)))?;
+            Some(UserSession { session, rsvp_status: rsvp.status })
+        })
+        .filter(|s| s.session.status == "scheduled" && session_end(&s.session) > now && s.rsvp_status != "declined")
+        .take(limit.min(MAX_PAGE_SIZE) as usize)
+        .collect()
+}
+
+// RSVPs for a session, with the waitlist in the order seats will be offered.
+#[ic_cdk::query]
+fn get_session_participants(session_id: u64) -> Result<Vec<SessionParticipant>, String> {
+    let caller = ic_cdk::caller();
+    let session = STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&session_id))
+        .ok_or("Session not found.".to_string())?;
+    viewable_group(session.group_id, caller)?;
+
+    let mut participants = session_participants(session_id);
+    participants.sort_by_key(|p| p.joined_at);
+    Ok(participants)
+}
+
+#[ic_cdk::update]
+fn create_task(
+    title: String,
+    description: String,
+    category: String,
+    difficulty: String,
+    token_reward: u32,
+    points_reward: u32,
+) -> Result<Task, String> {
+    let caller = ic_cdk::caller();
+    // TODO: Add check to ensure caller is an admin
+
+    let task_id = next_id("task");
+    let new_task = Task {
+        id: task_id,
+        public_id: task_id.to_string(),
+        title,
+        description,
+        category,
+        difficulty,
+        token_reward,
+        points_reward,
+        requirements: None,
+        is_active: true,
+        is_repeatable: false,
+        max_completions: 1,
+        created_by: caller,
+        created_at: ic_cdk::api::time(),
+        expires_at: None,
+        metadata: None,
+    };
+
+    TASKS.with(|tasks| {
+        tasks.borrow_mut().insert(task_id, new_task.clone());
+    });
+    if let Some(expires_at) = new_task.expires_at {
+        TASKS_BY_EXPIRY.with(|index| index.borrow_mut().insert((expires_at, task_id), ()));
+    }
+
+    Ok(new_task)
+}
+
+#[ic_cdk::update]
+fn complete_task(task_id: u64) -> Result<UserTaskCompletion, String> {
+    let caller = ic_cdk::caller();
+    
+    let task = TASKS.with(|tasks| tasks.borrow().get(&task_id))
+        .ok_or("Task not found.".to_string())?;
+
+    // TODO: Add validation to check if user has already completed the task
+
+    let completion_id = next_id("user_task_completion");
+    let new_completion = UserTaskCompletion {
+        id: completion_id,
+        user_id: caller,
+        task_id,
+        completed_at: ic_cdk::api::time(),
+        tokens_earned: task.token_reward,
+        points_earned: task.points_reward,
+        completion_count: 1,
+        proof_data: None,
+        metadata: None,
+    };
+
+    USER_TASK_COMPLETIONS.with(|completions| {
+        completions.borrow_mut().insert(completion_id, new_completion.clone());
+    });
+    TASK_COMPLETIONS_BY_USER.with(|index| {
+        index.borrow_mut().insert((caller, completion_id), ());
+    });
+    record_event(caller, "task_completed", task_id, None, task.title);
+
+    // TODO: Update user's token/point balance
+
+    Ok(new_completion)
+}
+
+#[ic_cdk::query]
+fn get_tasks() -> Vec<Task> {
+    TASKS.with(|tasks| {
+        tasks.borrow().iter().map(|(_, task)| task.clone()).collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_my_task_completions() -> Vec<UserTaskCompletion> {
+    let caller = ic_cdk::caller();
+    let ids = index_entries(&TASK_COMPLETIONS_BY_USER, caller);
+    USER_TASK_COMPLETIONS.with(|completions| {
+        let completions = completions.borrow();
+        ids.iter().filter_map(|id| completions.get(id)).collect()
+    })
+}
+
+// --- Achievement Methods ---
+
+#[ic_cdk::update]
+fn create_achievement(
+    title: String,
+    description: String,
+    category: String,
+    icon: Option<String>,
+    requirements: String,
+    reward_tokens: u32,
+    reward_points: u32,
+) -> Result<Achievement, String> {
+    let caller = ic_cdk::caller();
+    if !is_admin(caller) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    let title = title.trim().to_string();
+    if title.is_empty() {
+        return Err("Title cannot be empty.".to_string());
+    }
+
+    let achievement_id = next_id("achievement");
+    let achievement = Achievement {
+        id: achievement_id,
+        public_id: achievement_id.to_string(),
+        title,
+        description,
+        category,
+        icon,
+        requirements,
+        reward_tokens,
+        reward_points,
+        is_active: true,
+        created_at: ic_cdk::api::time(),
+        created_by: caller,
+    };
+    ACHIEVEMENTS.with(|achievements| {
+        achievements.borrow_mut().insert(achievement_id, achievement.clone());
+    });
+
+    Ok(achievement)
+}
+
+#[ic_cdk::query]
+fn get_achievements() -> Vec<Achievement> {
+    ACHIEVEMENTS.with(|achievements| {
+        achievements.borrow().iter().map(|(_, a)| a).filter(|a| a.is_active).collect()
+    })
+}
+
+// Unlocks an achievement for a user and posts it to their activity feed.
+// Admin only until achievements are unlocked automatically from their requirements.
+#[ic_cdk::update]
+fn award_achievement(user_id: Principal, achievement_id: u64) -> Result<UserAchievement, String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    let achievement = ACHIEVEMENTS.with(|achievements| achievements.borrow().get(&achievement_id))
+        .filter(|a| a.is_active)
+        .ok_or("Achievement not found.".to_string())?;
+    if USERS.with(|users| !users.borrow().contains_key(&user_id)) {
+        return Err("User not found.".to_string());
+    }
+    if USER_ACHIEVEMENTS_BY_USER.with(|index| index.borrow().contains_key(&(user_id, achievement_id))) {
+        return Err("This user has already unlocked this achievement.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    let user_achievement_id = next_id("user_achievement");
+    let unlocked = UserAchievement {
+        id: user_achievement_id,
+        user_id,
+        achievement_id,
+        progress: 100.0,
+        is_completed: true,
+        completed_at: Some(now),
+        tokens_earned: achievement.reward_tokens,
+        points_earned: achievement.reward_points,
+        created_at: now,
+        updated_at: now,
+    };
+    USER_ACHIEVEMENTS.with(|store| {
+        store.borrow_mut().insert(user_achievement_id, unlocked.clone());
+    });
+    USER_ACHIEVEMENTS_BY_USER.with(|index| {
+        index.borrow_mut().insert((user_id, achievement_id), user_achievement_id);
+    });
+    record_event(user_id, "achievement_unlocked", achievement_id, None, achievement.title);
+
+    Ok(unlocked)
+}
+
+#[ic_cdk::query]
+fn get_my_achievements() -> Vec<UserAchievement> {
+    let caller = ic_cdk::caller();
+    let ids: Vec<u64> = USER_ACHIEVEMENTS_BY_USER.with(|index| {
+        index
+            .borrow()
+            .range((caller, 0)..=(caller, u64::MAX))
+            .map(|(_, id)| id)
+            .collect()
+    });
+    USER_ACHIEVEMENTS.with(|store| {
+        let store = store.borrow();
+        ids.iter().filter_map(|id| store.get(id)).collect()
+    })
+}
+
+// --- Learning Metrics Methods ---
+
+#[ic_cdk::update]
+fn record_learning_activity(activity: LearningActivity) -> Result<LearningMetrics, String> {
+    let caller = ic_cdk::caller();
+
+    if activity.minutes > MINUTES_PER_DAY {
+        return Err("Activity cannot exceed one day.".to_string());
+    }
+    let topic = match &activity.topic {
+        Some(topic) => Some(normalize_topic(topic).ok_or("Topic cannot be empty.".to_string())?),
+        None => None,
+    };
+    if let Some(score) = activity.comprehension_score {
+        if !(0.0..=100.0).contains(&score) {
+            return Err("Comprehension score must be between 0 and 100.".to_string());
+        }
+        if topic.is_none() {
+            return Err("A topic is required when reporting a comprehension score.".to_string());
+        }
+    }
+
+    let now = ic_cdk::api::time();
+    let mut metrics = daily_metrics(caller, now);
+
+    metrics.time_spent_minutes = (metrics.time_spent_minutes + activity.minutes).min(MINUTES_PER_DAY);
+    metrics.messages_sent = metrics.messages_sent.saturating_add(activity.messages_sent);
+    metrics.modules_completed = metrics.modules_completed.saturating_add(activity.modules_completed);
+    if let Some(session_id) = activity.session_id {
+        if !metrics.session_ids.contains(&session_id) {
+            metrics.session_ids.push(session_id);
+        }
+    }
+    if let (Some(topic), Some(score)) = (topic, activity.comprehension_score) {
+        let (_, adjustment) = apply_difficulty_signal(caller, &topic, score, "tutor session", now);
+        metrics.difficulty_adjustments.extend(adjustment);
+        metrics.comprehension_scores.push(ComprehensionScore {
+            topic,
+            score,
+            session_id: activity.session_id,
+            recorded_at: now,
+        });
+    }
+    metrics.engagement_metrics.activity_count += 1;
+    metrics.engagement_metrics.last_activity_at = now;
+
+    LEARNING_METRICS.with(|store| {
+        store.borrow_mut().insert((caller, day_index(now)), metrics.clone());
+    });
+
+    Ok(metrics)
+}
+
+#[ic_cdk::query]
+fn get_learning_metrics(from: u64, to: u64) -> Vec<LearningMetrics> {
+    let caller = ic_cdk::caller();
+    if from > to {
+        return vec![];
+    }
+    LEARNING_METRICS.with(|metrics| {
+        metrics
+            .borrow()
+            .range((caller, day_index(from))..=(caller, day_index(to)))
+            .map(|(_, m)| m)
+            .collect()
+    })
+}
+
+// Returns `count` buckets of time spent, oldest first, ending with the current
+// day or week. Weeks start on Monday (UTC).
+#[ic_cdk::query]
+fn get_time_spent(period: String, count: u32) -> Result<Vec<TimeSpentBucket>, String> {
+    let caller = ic_cdk::caller();
+    if count == 0 || count > 366 {
+        return Err("Count must be between 1 and 366.".to_string());
+    }
+
+    let today = day_index(ic_cdk::api::time());
+    let (bucket_days, current_start) = match period.as_str() {
+        "day" => (1, today),
+        "week" => (7, today - (today + 3) % 7),
+        _ => return Err("Period must be \"day\" or \"week\".".to_string()),
+    };
+    let first_start = current_start.saturating_sub((count as u64 - 1) * bucket_days);
+
+    let mut buckets: Vec<TimeSpentBucket> = (0..count as u64)
+        .map(|i| TimeSpentBucket {
+            period_start: (first_start + i * bucket_days) * NANOS_PER_DAY,
+            minutes: 0,
+            messages_sent: 0,
+            active_days: 0,
+        })
+        .collect();
+
+    LEARNING_METRICS.with(|metrics| {
+        for ((_, day), m) in metrics.borrow().range((caller, first_start)..=(caller, today)) {
+            if let Some(bucket) = buckets.get_mut(((day - first_start) / bucket_days) as usize) {
+                bucket.minutes += m.time_spent_minutes;
+                bucket.messages_sent += m.messages_sent;
+                bucket.active_days += 1;
+            }
+        }
+    });
+
+    Ok(buckets)
+}
+
+#[ic_cdk::query]
+fn get_learning_streak() -> LearningStreak {
+    let caller = ic_cdk::caller();
+    let today = day_index(ic_cdk::api::time());
+
+    let mut current_run = 0u32;
+    let mut longest_run = 0u32;
+    let mut last_day: Option<u64> = None;
+
+    LEARNING_METRICS.with(|metrics| {
+        for ((_, day), _) in metrics.borrow().range((caller, 0)..=(caller, u64::MAX)) {
+            current_run = match last_day {
+                Some(previous) if previous + 1 == day => current_run + 1,
+                _ => 1,
+            };
+            longest_run = longest_run.max(current_run);
+            last_day = Some(day);
+        }
+    });
+
+    // A streak is still alive if the learner was active today or yesterday.
+    let current_days = match last_day {
+        Some(day) if day + 1 >= today => current_run,
+        _ => 0,
+    };
+
+    LearningStreak {
+        current_days,
+        longest_days: longest_run,
+        last_active_date: last_day.map(|day| day * NANOS_PER_DAY),
+    }
+}
+
+#[ic_cdk::query]
+fn get_comprehension_trend(topic: String, days: u32) -> Result<Vec<ComprehensionTrendPoint>, String> {
+    let caller = ic_cdk::caller();
+    let topic = normalize_topic(&topic).ok_or("Topic cannot be empty.".to_string())?;
+    if days == 0 || days > 366 {
+        return Err("Days must be between 1 and 366.".to_string());
+    }
+
+    let today = day_index(ic_cdk::api::time());
+    let first_day = today.saturating_sub(days as u64 - 1);
+
+    Ok(LEARNING_METRICS.with(|metrics| {
+        metrics
+            .borrow()
+            .range((caller, first_day)..=(caller, today))
+            .filter_map(|(_, m)| {
+                let scores: Vec<f32> = m.comprehension_scores
+                    .iter()
+                    .filter(|s| s.topic == topic)
+                    .map(|s| s.score)
+                    .collect();
+                if scores.is_empty() {
+                    return None;
+                }
+                Some(ComprehensionTrendPoint {
+                    date: m.date,
+                    average_score: scores.iter().sum::<f32>() / scores.len() as f32,
+                    samples: scores.len() as u32,
+                })
+            })
+            .collect()
+    }))
+}
+
+// --- Adaptive Difficulty Methods ---
+
+#[ic_cdk::update]
+fn submit_quiz_result(topic: String, score: f32, max_score: f32) -> Result<TopicDifficulty, String> {
+    let caller = ic_cdk::caller();
+    let topic = normalize_topic(&topic).ok_or("Topic cannot be empty.".to_string())?;
+    if !score.is_finite() || !max_score.is_finite() || max_score <= 0.0 || score < 0.0 || score > max_score {
+        return Err("Score must be between 0 and the maximum score.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    let (difficulty, adjustment) = apply_difficulty_signal(caller, &topic, score / max_score * 100.0, "quiz", now);
+
+    let mut metrics = daily_metrics(caller, now);
+    metrics.difficulty_adjustments.extend(adjustment);
+    metrics.engagement_metrics.activity_count += 1;
+    metrics.engagement_metrics.last_activity_at = now;
+    LEARNING_METRICS.with(|store| {
+        store.borrow_mut().insert((caller, day_index(now)), metrics);
+    });
+
+    Ok(difficulty)
+}
+
+#[ic_cdk::query]
+fn get_topic_difficulty(topic: String) -> Result<String, String> {
+    let caller = ic_cdk::caller();
+    let topic = normalize_topic(&topic).ok_or("Topic cannot be empty.".to_string())?;
+    Ok(effective_difficulty(caller, &topic))
+}
+
+#[ic_cdk::query]
+fn get_difficulty_profile() -> Vec<TopicDifficulty> {
+    let caller = ic_cdk::caller();
+    DIFFICULTY_PROFILES.with(|profiles| profiles.borrow().get(&caller))
+        .map(|profile| profile.topics)
+        .unwrap_or_default()
+}
+
+// Most recent adjustments first, optionally restricted to a single topic.
+#[ic_cdk::query]
+fn get_difficulty_adjustments(topic: Option<String>, limit: u32) -> Vec<DifficultyAdjustment> {
+    let caller = ic_cdk::caller();
+    let topic = topic.and_then(|t| normalize_topic(&t));
+    DIFFICULTY_ADJUSTMENTS.with(|adjustments| {
+        adjustments
+            .borrow()
+            .range((caller, 0)..=(caller, u64::MAX))
+            .rev()
+            .map(|(_, adjustment)| adjustment)
+            .filter(|adjustment| topic.as_ref().is_none_or(|t| &adjustment.topic == t))
+            .take(limit as usize)
+            .collect()
+    })
+}
+
+// --- Learning Path Methods ---
+
+#[ic_cdk::update]
+fn create_learning_path_admin(
+    title: String,
+    description: String,
+    level: String,
+    duration: String,
+    thumbnail: Option<String>,
+    tags: Vec<String>,
+    modules: Vec<LearningPathModule>,
+) -> Result<LearningPath, String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    if !DIFFICULTY_LEVELS.contains(&level.as_str()) {
+        return Err("Level must be beginner, intermediate or advanced.".to_string());
+    }
+
+    let mut tags: Vec<String> = tags.iter().filter_map(|t| normalize_topic(t)).collect();
+    tags.sort();
+    tags.dedup();
+
+    // Module ids are only unique within their path, so assign them here.
+    let modules = modules
+        .into_iter()
+        .enumerate()
+        .map(|(i, module)| LearningPathModule { id: i as u64 + 1, ..module })
+        .collect();
+
+    let path_id = next_id("learning_path");
+    let new_path = LearningPath {
+        id: path_id,
+        title,
+        description,
+        level,
+        duration,
+        thumbnail,
+        tags,
+        created_at: ic_cdk::api::time(),
+        updated_at: ic_cdk::api::time(),
+        modules,
+        enrollment_count: 0,
+        completion_count: 0,
+        topic_ids: vec![],
+    };
+
+    LEARNING_PATHS.with(|paths| {
+        paths.borrow_mut().insert(path_id, new_path.clone());
+    });
+
+    Ok(new_path)
+}
+
+#[ic_cdk::query]
+fn get_learning_paths() -> Vec<LearningPath> {
+    LEARNING_PATHS.with(|paths| {
+        paths.borrow().iter().map(|(_, path)| path).collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_learning_path(id: u64) -> Option<LearningPath> {
+    LEARNING_PATHS.with(|paths| paths.borrow().get(&id))
+}
+
+#[ic_cdk::update]
+fn enroll_learning_path(path_id: u64) -> Result<LearningPathProgress, String> {
+    let caller = ic_cdk::caller();
+
+    let mut path = LEARNING_PATHS.with(|paths| paths.borrow().get(&path_id))
+        .ok_or("Learning path not found.".to_string())?;
+
+    if LEARNING_PATH_PROGRESS.with(|progress| progress.borrow().contains_key(&(caller, path_id))) {
+        return Err("You are already enrolled in this learning path.".to_string());
+    }
+
+    let progress_id = next_id("learning_path_progress");
+    let new_progress = LearningPathProgress {
+        id: progress_id,
+        user_id: caller,
+        learning_path_id: path_id,
+        current_module_id: path.modules.first().map(|m| m.id),
+        progress_percentage: 0.0,
+        last_activity: ic_cdk::api::time(),
+        enrolled_at: ic_cdk::api::time(),
+        completed_at: None,
+    };
+
+    LEARNING_PATH_PROGRESS.with(|progress| {
+        progress.borrow_mut().insert((caller, path_id), new_progress.clone());
+    });
+
+    path.enrollment_count += 1;
+    LEARNING_PATHS.with(|paths| {
+        paths.borrow_mut().insert(path_id, path);
+    });
+
+    Ok(new_progress)
+}
+
+// Moves the caller's bookmark within a path. Progress itself only advances
+// through `complete_learning_path_module`.
+#[ic_cdk::update]
+fn update_learning_path_progress(path_id: u64, current_module_id: Option<u64>) -> Result<LearningPathProgress, String> {
+    let caller = ic_cdk::caller();
+    let path = LEARNING_PATHS.with(|paths| paths.borrow().get(&path_id))
+        .ok_or("Learning path not found.".to_string())?;
+    let mut progress = LEARNING_PATH_PROGRESS.with(|progress| progress.borrow().get(&(caller, path_id)))
+        .ok_or("You are not enrolled in this learning path.".to_string())?;
+
+    if let Some(module_id) = current_module_id {
+        if !path.modules.iter().any(|m| m.id == module_id) {
+            return Err("Module not found in this learning path.".to_string());
+        }
+    }
+
+    progress.current_module_id = current_module_id;
+    progress.last_activity = ic_cdk::api::time();
+    LEARNING_PATH_PROGRESS.with(|store| {
+        store.borrow_mut().insert((caller, path_id), progress.clone());
+    });
+
+    Ok(progress)
+}
+
+// Records that the caller finished a module. The progress percentage is the
+// share of the path's modules completed, and the path is completed (and a
+// certificate issued) once every module has been.
+#[ic_cdk::update]
+fn complete_learning_path_module(path_id: u64, module_id: u64) -> Result<LearningPathProgress, String> {
+    let caller = ic_cdk::caller();
+    let path = LEARNING_PATHS.with(|paths| paths.borrow().get(&path_id))
+        .ok_or("Learning path not found.".to_string())?;
+    let mut progress = LEARNING_PATH_PROGRESS.with(|progress| progress.borrow().get(&(caller, path_id)))
+        .ok_or("You are not enrolled in this learning path.".to_string())?;
+    if !path.modules.iter().any(|m| m.id == module_id) {
+        return Err("Module not found in this learning path.".to_string());
+    }
+    if PATH_MODULE_COMPLETIONS.with(|completions| completions.borrow().contains_key(&(caller, path_id, module_id))) {
+        return Err("You have already completed this module.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    let completion = ModuleCompletion {
+        id: next_id("module_completion"),
+        user_id: caller,
+        module_id,
+        completed: true,
+        completion_date: Some(now),
+        learning_path_id: path_id,
+    };
+    PATH_MODULE_COMPLETIONS.with(|completions| {
+        completions.borrow_mut().insert((caller, path_id, module_id), completion);
+    });
+
+    let completed = completed_path_modules(caller, path_id);
+    let mut remaining: Vec<&LearningPathModule> = path.modules.iter().filter(|m| !completed.contains(&m.id)).collect();
+    remaining.sort_by_key(|m| m.order);
+    progress.progress_percentage = (path.modules.len() - remaining.len()) as f32 / path.modules.len() as f32 * 100.0;
+    progress.current_module_id = remaining.first().map(|m| m.id);
+    progress.last_activity = now;
+
+    if remaining.is_empty() && progress.completed_at.is_none() {
+        complete_learning_path(caller, path, &mut progress, now);
+    }
+    LEARNING_PATH_PROGRESS.with(|store| {
+        store.borrow_mut().insert((caller, path_id), progress.clone());
+    });
+
+    Ok(progress)
+}
+
+// Marks an enrolled learner as having completed a path, e.g. after an
+// assessment done outside the canister. Admin only.
+#[ic_cdk::update]
+fn attest_learning_path_completion(path_id: u64, user_id: Principal) -> Result<LearningPathProgress, String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    let path = LEARNING_PATHS.with(|paths| paths.borrow().get(&path_id))
+        .ok_or("Learning path not found.".to_string())?;
+    let mut progress = LEARNING_PATH_PROGRESS.with(|progress| progress.borrow().get(&(user_id, path_id)))
+        .ok_or("This user is not enrolled in this learning path.".to_string())?;
+    if progress.completed_at.is_some() {
+        return Err("This user has already completed this learning path.".to_string());
+    }
+
+    let now = ic_cdk::api::time();
+    progress.progress_percentage = 100.0;
+    progress.current_module_id = None;
+    progress.last_activity = now;
+    complete_learning_path(user_id, path, &mut progress, now);
+    LEARNING_PATH_PROGRESS.with(|store| {
+        store.borrow_mut().insert((user_id, path_id), progress.clone());
+    });
+
+    Ok(progress)
+}
+
+#[ic_cdk::query]
+fn get_my_module_completions(path_id: u64) -> Vec<ModuleCompletion> {
+    let caller = ic_cdk::caller();
+    PATH_MODULE_COMPLETIONS.with(|completions| {
+        completions
+            .borrow()
+            .range((caller, path_id, 0)..=(caller, path_id, u64::MAX))
+            .map(|(_, completion)| completion)
+            .collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_my_learning_paths() -> Vec<LearningPathProgress> {
+    let caller = ic_cdk::caller();
+    LEARNING_PATH_PROGRESS.with(|progress| {
+        progress
+            .borrow()
+            .range((caller, 0)..=(caller, u64::MAX))
+            .map(|(_, p)| p)
+            .collect()
+    })
+}
+
+// Ranks learning paths the caller has not yet enrolled in. Each signal adds to
+// the score and contributes a human-readable reason for the frontend.
+#[ic_cdk::query]
+fn recommend_learning_paths(limit: u32) -> Vec<PathRecommendation> {
+    let caller = ic_cdk::caller();
+
+    let interests = USERS.with(|users| users.borrow().get(&caller))
+        .map(|user| user.settings.interests)
+        .unwrap_or_default();
+
+    let enrolled = get_my_learning_paths();
+    let completed_tags: Vec<String> = LEARNING_PATHS.with(|paths| {
+        let paths = paths.borrow();
+        enrolled
+            .iter()
+            .filter(|p| p.completed_at.is_some())
+            .filter_map(|p| paths.get(&p.learning_path_id))
+            .flat_map(|path| path.tags)
+            .collect()
+    });
+
+    let topic_levels = DIFFICULTY_PROFILES.with(|profiles| profiles.borrow().get(&caller))
+        .map(|profile| profile.topics)
+        .unwrap_or_default();
+    let default_level = default_difficulty(caller);
+
+    // Count how many of the caller's connections and group mates are on each path.
+    let count_paths = |users: Vec<Principal>| {
+        let mut counts: HashMap<u64, u32> = HashMap::new();
+        LEARNING_PATH_PROGRESS.with(|progress| {
+            let progress = progress.borrow();
+            for user in users {
+                for ((_, path_id), _) in progress.range((user, 0)..=(user, u64::MAX)) {
+                    *counts.entry(path_id).or_default() += 1;
+                }
+            }
+        });
+        counts
+    };
+    let connection_counts = count_paths(connected_users(caller));
+    let group_counts = count_paths(group_mates(caller));
+
+    let mut recommendations: Vec<PathRecommendation> = get_learning_paths()
+        .into_iter()
+        .filter(|path| !enrolled.iter().any(|p| p.learning_path_id == path.id))
+        .map(|path| {
+            let mut score = 0.0f32;
+            let mut reasons = vec![];
+
+            let matching: Vec<&String> = path.tags.iter().filter(|t| interests.contains(t)).collect();
+            if !matching.is_empty() {
+                score += 3.0 * matching.len() as f32;
+                reasons.push(format!(
+                    "Matches your interests: {}",
+                    matching.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(", ")
+                ));
+            }
+
+            let builds_on = path.tags.iter().filter(|t| completed_tags.contains(t)).count().min(3);
+            if builds_on > 0 {
+                score += builds_on as f32;
+                reasons.push("Builds on paths you have completed".to_string());
+            }
+
+            // Compare against the learner's level on the path's own topics when known.
+            let learner_level = path.tags
+                .iter()
+                .find_map(|tag| topic_levels.iter().find(|t| &t.topic == tag))
+                .map(|t| t.level.clone())
+                .unwrap_or_else(|| default_level.clone());
+            let level_gap = level_rank(&path.level).abs_diff(level_rank(&learner_level));
+            match level_gap {
+                0 => {
+                    score += 2.0;
+                    reasons.push(format!("Suited to your {} level", learner_level));
+                }
+                1 => score += 0.5,
+                _ => score -= 1.0,
+            }
+
+            if let Some(&count) = connection_counts.get(&path.id) {
+                score += 1.5 * count.min(5) as f32;
+                reasons.push(format!("{} of your connections are taking this path", count));
+            }
+            if let Some(&count) = group_counts.get(&path.id) {
+                score += count.min(5) as f32;
+                reasons.push(format!("{} members of your study groups are taking this path", count));
+            }
+
+            if path.enrollment_count > 0 {
+                score += (1.0 + path.enrollment_count as f32).ln();
+                reasons.push(format!("{} learners enrolled", path.enrollment_count));
+            }
+
+            PathRecommendation { path, score, reasons }
+        })
+        .collect();
+
+    recommendations.sort_by(|a, b| b.score.total_cmp(&a.score));
+    recommendations.truncate(limit as usize);
+    recommendations
+}
+
+// --- Topic Methods ---
+
+#[ic_cdk::update]
+fn create_topic_admin(
+    name: String,
+    description: Option<String>,
+    parent_id: Option<u64>,
+    difficulty_level: Option<String>,
+    keywords: Option<String>,
+) -> Result<Topic, String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Topic name cannot be empty.".to_string());
+    }
+    if let Some(parent_id) = parent_id {
+        if !TOPICS.with(|topics| topics.borrow().contains_key(&parent_id)) {
+            return Err("Parent topic not found.".to_string());
+        }
+    }
+    if let Some(level) = &difficulty_level {
+        if !DIFFICULTY_LEVELS.contains(&level.as_str()) {
+            return Err("Difficulty level must be beginner, intermediate or advanced.".to_string());
+        }
+    }
+
+    let topic_id = next_id("topic");
+    let new_topic = Topic {
+        id: topic_id,
+        name,
+        description,
+        parent_id,
+        difficulty_level,
+        keywords,
+        created_at: ic_cdk::api::time(),
+    };
+
+    TOPICS.with(|topics| {
+        topics.borrow_mut().insert(topic_id, new_topic.clone());
+    });
+
+    Ok(new_topic)
+}
+
+#[ic_cdk::update]
+fn update_topic_admin(
+    id: u64,
+    name: String,
+    description: Option<String>,
+    parent_id: Option<u64>,
+    difficulty_level: Option<String>,
+    keywords: Option<String>,
+) -> Result<Topic, String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    let topic = TOPICS.with(|topics| topics.borrow().get(&id))
+        .ok_or("Topic not found.".to_string())?;
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Topic name cannot be empty.".to_string());
+    }
+    if let Some(parent_id) = parent_id {
+        if !TOPICS.with(|topics| topics.borrow().contains_key(&parent_id)) {
+            return Err("Parent topic not found.".to_string());
+        }
+        if topic_subtree_ids(id).contains(&parent_id) {
+            return Err("A topic cannot be moved under itself or one of its subtopics.".to_string());
+        }
+    }
+    if let Some(level) = &difficulty_level {
+        if !DIFFICULTY_LEVELS.contains(&level.as_str()) {
+            return Err("Difficulty level must be beginner, intermediate or advanced.".to_string());
+        }
+    }
+
+    let updated_topic = Topic {
+        name,
+        description,
+        parent_id,
+        difficulty_level,
+        keywords,
+        ..topic
+    };
+    TOPICS.with(|topics| {
+        topics.borrow_mut().insert(id, updated_topic.clone());
+    });
+
+    Ok(updated_topic)
+}
+
+// Removes a leaf topic and untags everything that referenced it.
+#[ic_cdk::update]
+fn delete_topic_admin(id: u64) -> Result<(), String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    if !TOPICS.with(|topics| topics.borrow().contains_key(&id)) {
+        return Err("Topic not found.".to_string());
+    }
+    if !get_topic_children(Some(id)).is_empty() {
+        return Err("Move or delete this topic's subtopics first.".to_string());
+    }
+
+    for group_id in topic_index_entries(&TOPIC_GROUPS, id) {
+        STUDY_GROUPS.with(|groups| {
+            let mut groups = groups.borrow_mut();
+            if let Some(mut group) = groups.get(&group_id) {
+                group.topic_id = None;
+                groups.insert(group_id, group);
+            }
+        });
+        TOPIC_GROUPS.with(|index| index.borrow_mut().remove(&(id, group_id)));
+    }
+    for path_id in topic_index_entries(&TOPIC_PATHS, id) {
+        LEARNING_PATHS.with(|paths| {
+            let mut paths = paths.borrow_mut();
+            if let Some(mut path) = paths.get(&path_id) {
+                path.topic_ids.retain(|t| *t != id);
+                paths.insert(path_id, path);
+            }
+        });
+        TOPIC_PATHS.with(|index| index.borrow_mut().remove(&(id, path_id)));
+    }
+    for tutor_id in topic_index_entries(&TOPIC_TUTORS, id) {
+        TUTORS.with(|tutors| {
+            let mut tutors = tutors.borrow_mut();
+            if let Some(mut tutor) = tutors.get(&tutor_id) {
+                tutor.topic_ids.retain(|t| *t != id);
+                tutors.insert(tutor_id, tutor);
+            }
+        });
+        TOPIC_TUTORS.with(|index| index.borrow_mut().remove(&(id, tutor_id)));
+    }
+
+    TOPICS.with(|topics| topics.borrow_mut().remove(&id));
+    Ok(())
+}
+
+#[ic_cdk::query]
+fn get_topics() -> Vec<Topic> {
+    TOPICS.with(|topics| topics.borrow().iter().map(|(_, topic)| topic).collect())
+}
+
+#[ic_cdk::query]
+fn get_topic(id: u64) -> Option<Topic> {
+    TOPICS.with(|topics| topics.borrow().get(&id))
+}
+
+// Direct children of a topic, or the root topics when no parent is given.
+#[ic_cdk::query]
+fn get_topic_children(parent_id: Option<u64>) -> Vec<Topic> {
+    TOPICS.with(|topics| {
+        topics
+            .borrow()
+            .iter()
+            .filter(|(_, topic)| topic.parent_id == parent_id)
+            .map(|(_, topic)| topic)
+            .collect()
+    })
+}
+
+// The topic followed by all of its descendants, breadth first.
+#[ic_cdk::query]
+fn get_topic_subtree(id: u64) -> Result<Vec<Topic>, String> {
+    if !TOPICS.with(|topics| topics.borrow().contains_key(&id)) {
+        return Err("Topic not found.".to_string());
+    }
+    Ok(TOPICS.with(|topics| {
+        let topics = topics.borrow();
+        topic_subtree_ids(id).into_iter().filter_map(|t| topics.get(&t)).collect()
+    }))
+}
+
+// Breadcrumb from the root topic down to (and including) the given topic.
+#[ic_cdk::query]
+fn get_topic_ancestors(id: u64) -> Result<Vec<Topic>, String> {
+    let topic = TOPICS.with(|topics| topics.borrow().get(&id))
+        .ok_or("Topic not found.".to_string())?;
+    let mut ancestors = vec![topic];
+    while let Some(parent_id) = ancestors.last().and_then(|t| t.parent_id) {
+        match TOPICS.with(|topics| topics.borrow().get(&parent_id)) {
+            Some(parent) => ancestors.push(parent),
+            None => break,
+        }
+    }
+    ancestors.reverse();
+    Ok(ancestors)
+}
+
+#[ic_cdk::update]
+fn set_study_group_topic(group_id: u64, topic_id: Option<u64>) -> Result<StudyGroup, String> {
+    let caller = ic_cdk::caller();
+    let mut group = writable_group(group_id)?;
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can perform this action.".to_string());
+    }
+    let new_topics = validate_topic_ids(topic_id.into_iter().collect())?;
+
+    let old_topics: Vec<u64> = group.topic_id.into_iter().collect();
+    update_topic_index(&TOPIC_GROUPS, group_id, &old_topics, &new_topics);
+
+    group.topic_id = topic_id;
+    group.updated_at = ic_cdk::api::time();
+    STUDY_GROUPS.with(|groups| {
+        groups.borrow_mut().insert(group_id, group.clone());
+    });
+
+    Ok(group)
+}
+
+#[ic_cdk::update]
+fn set_learning_path_topics_admin(path_id: u64, topic_ids: Vec<u64>) -> Result<LearningPath, String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    let mut path = LEARNING_PATHS.with(|paths| paths.borrow().get(&path_id))
+        .ok_or("Learning path not found.".to_string())?;
+    let topic_ids = validate_topic_ids(topic_ids)?;
+
+    update_topic_index(&TOPIC_PATHS, path_id, &path.topic_ids, &topic_ids);
+
+    path.topic_ids = topic_ids;
+    path.updated_at = ic_cdk::api::time();
+    LEARNING_PATHS.with(|paths| {
+        paths.borrow_mut().insert(path_id, path.clone());
+    });
+
+    Ok(path)
+}
+
+#[ic_cdk::update]
+fn set_tutor_topics(tutor_id: u64, topic_ids: Vec<u64>) -> Result<Tutor, String> {
+    let mut tutor = TUTORS.with(|tutors| tutors.borrow().get(&tutor_id))
+        .ok_or("Tutor not found.".to_string())?;
+    if tutor.user_id != ic_cdk::caller() {
+        return Err("You are not authorized to edit this tutor.".to_string());
+    }
+    let topic_ids = validate_topic_ids(topic_ids)?;
+
+    update_topic_index(&TOPIC_TUTORS, tutor_id, &tutor.topic_ids, &topic_ids);
+
+    tutor.topic_ids = topic_ids;
+    tutor.updated_at = ic_cdk::api::time();
+    TUTORS.with(|tutors| {
+        tutors.borrow_mut().insert(tutor_id, tutor.clone());
+    });
+
+    Ok(tutor)
+}
+
+// Archived groups are left out, as are private groups and tutors the caller
+// cannot see: private groups show up for their members only, private tutors
+// for their owner only.
+#[ic_cdk::query]
+fn browse_topic(topic_id: u64, include_subtopics: bool) -> Result<TopicContents, String> {
+    let caller = ic_cdk::caller();
+    let topics = if include_subtopics {
+        get_topic_subtree(topic_id)?
+    } else {
+        vec![get_topic(topic_id).ok_or("Topic not found.".to_string())?]
+    };
+
+    let mut group_ids: Vec<u64> = topics.iter().flat_map(|t| topic_index_entries(&TOPIC_GROUPS, t.id)).collect();
+    let mut path_ids: Vec<u64> = topics.iter().flat_map(|t| topic_index_entries(&TOPIC_PATHS, t.id)).collect();
+    let mut tutor_ids: Vec<u64> = topics.iter().flat_map(|t| topic_index_entries(&TOPIC_TUTORS, t.id)).collect();
+    for ids in [&mut group_ids, &mut path_ids, &mut tutor_ids] {
+        ids.sort();
+        ids.dedup();
+    }
+
+    Ok(TopicContents {
+        topics,
+        study_groups: STUDY_GROUPS.with(|groups| {
+            let groups = groups.borrow();
+            group_ids
+                .iter()
+                .filter_map(|id| groups.get(id))
+                .filter(|group| !group.is_archived)
+                .filter(|group| !group.is_private || active_membership(group.id, caller).is_ok())
+                .collect()
+        }),
+        learning_paths: LEARNING_PATHS.with(|paths| {
+            let paths = paths.borrow();
+            path_ids.iter().filter_map(|id| paths.get(id)).collect()
+        }),
+        tutors: TUTORS.with(|tutors| {
+            let tutors = tutors.borrow();
+            tutor_ids
+                .iter()
+                .filter_map(|id| tutors.get(id))
+                .filter(|tutor| tutor.is_public || tutor.user_id == caller)
+                .collect()
+        }),
+    })
+}
+
+// --- Certificate Methods ---
+
+#[ic_cdk::query]
+fn get_my_certificates() -> Vec<Certificate> {
+    let caller = ic_cdk::caller();
+    let ids = index_entries(&CERTIFICATES_BY_USER, caller);
+    CERTIFICATES.with(|certificates| {
+        let certificates = certificates.borrow();
+        ids.iter().filter_map(|id| certificates.get(id)).collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_certificate(id: u64) -> Option<Certificate> {
+    CERTIFICATES.with(|certificates| certificates.borrow().get(&id))
+}
+
+// Must be called as a query: the data certificate is only available there.
+#[ic_cdk::query]
+fn get_certificate_proof(id: u64) -> Result<CertificateProof, String> {
+    let certificate = get_certificate(id).ok_or("Certificate not found.".to_string())?;
+
+    let position = CERTIFICATE_POSITIONS.with(|positions| positions.borrow().get(&id))
+        .ok_or("Certificate has not been certified.".to_string())?;
+    let leaf_count = CERTIFICATE_POSITIONS.with(|positions| positions.borrow().len());
+    let (leaf, proof) = CERTIFICATE_TREE.with(|tree| {
+        let tree = tree.borrow();
+        let node = |level: u8, index: u64| tree.get(&(level, index)).expect("missing Merkle tree node");
+        (node(0, position), certification::proof(position, leaf_count, node))
+    });
+
+    Ok(CertificateProof {
+        certificate,
+        leaf_hash: leaf.to_vec(),
+        proof: proof
+            .into_iter()
+            .map(|(sibling, sibling_is_left)| MerkleProofStep {
+                sibling: sibling.to_vec(),
+                sibling_is_left,
+            })
+            .collect(),
+        root_hash: certificate_root().to_vec(),
+        data_certificate: ic_cdk::api::data_certificate(),
+    })
+}
+
+// --- Calendar Feed Methods ---
+
+// Creates a new secret calendar feed token for the caller, invalidating the old
+// one. The feed is served at `/calendar/<token>.ics`.
+#[ic_cdk::update]
+async fn rotate_calendar_token() -> Result<String, String> {
+    let caller = ic_cdk::caller();
+    if USERS.with(|users| !users.borrow().contains_key(&caller)) {
+        return Err("User not found.".to_string());
+    }
+
+    let (bytes,) = ic_cdk::api::management_canister::main::raw_rand()
+        .await
+        .map_err(|(_, msg)| format!("Failed to generate calendar token: {}", msg))?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    revoke_calendar_token_for(caller);
+    CALENDAR_TOKENS.with(|tokens| {
+        tokens.borrow_mut().insert(token.clone(), caller);
+    });
+    USER_CALENDAR_TOKENS.with(|tokens| {
+        tokens.borrow_mut().insert(caller, token.clone());
+    });
+
+    Ok(token)
+}
+
+#[ic_cdk::query]
+fn get_calendar_token() -> Option<String> {
+    let caller = ic_cdk::caller();
+    USER_CALENDAR_TOKENS.with(|tokens| tokens.borrow().get(&caller))
+}
+
+#[ic_cdk::update]
+fn revoke_calendar_token() -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    if !revoke_calendar_token_for(caller) {
+        return Err("You do not have a calendar feed.".to_string());
+    }
+    Ok(())
+}
+
+// Serves calendar feeds. Responses are not certified, so feeds must be fetched
+// through the raw domain.
+#[ic_cdk::query]
+fn http_request(request: HttpRequest) -> HttpResponse {
+    let path = request.url.split('?').next().unwrap_or_default();
+    let token = path
+        .strip_prefix("/calendar/")
+        .and_then(|rest| rest.strip_suffix(".ics"));
+
+    let user = token.and_then(|token| CALENDAR_TOKENS.with(|tokens| tokens.borrow().get(&token.to_string())));
+    match user {
+        Some(user) if request.method == "GET" || request.method == "HEAD" => {
+            let now = ic_cdk::api::time();
+            let body = ical::calendar("Cogni study calendar", &calendar_events(user, now), now);
+            HttpResponse {
+                status_code: 200,
+                headers: vec![
+                    ("Content-Type".to_string(), "text/calendar; charset=utf-8".to_string()),
+                    ("Cache-Control".to_string(), "private, max-age=300".to_string()),
+                ],
+                body: body.into_bytes(),
+            }
+        }
+        Some(_) => HttpResponse {
+            status_code: 405,
+            headers: vec![("Allow".to_string(), "GET, HEAD".to_string())],
+            body: vec![],
+        },
+        None => HttpResponse {
+            status_code: 404,
+            headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+            body: b"Not found".to_vec(),
+        },
+    }
+}
+
+// --- Admin Methods ---
+
+#[ic_cdk::query]
+fn get_all_users_admin() -> Result<Vec<User>, String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    Ok(USERS.with(|users| users.borrow().iter().map(|(_, user)| user.clone()).collect()))
+}
+
+#[ic_cdk::update]
+fn update_user_status_admin(user_id: Principal, status: String) -> Result<User, String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    
+    USERS.with(|users| {
+        let mut users_mut = users.borrow_mut();
+        if let Some(mut user) = users_mut.get(&user_id) {
+            user.status = status;
+            users_mut.insert(user_id, user.clone());
+            Ok(user)
+        } else {
+            Err("User not found.".to_string())
+        }
+    })
+}
+
+// --- Billing Methods (Placeholders) ---
+
+// TODO: Implement full logic for creating subscription plans
+#[ic_cdk::update]
+fn create_subscription_plan_admin(/* params */) -> Result<(), String> {
+    if !is_admin(ic_cdk::caller()) {
+        return Err("Only admins can perform this action.".to_string());
+    }
+    // Placeholder
+    Ok(())
+}
+
+// TODO: Implement logic for creating a new subscription (HTTPS outcall to Paystack)
+#[ic_cdk::update]
+fn create_subscription(/* params */) -> Result<(), String> {
+    // Placeholder
+    Ok(())
+}
+
+
+// --- Blockchain Methods (Placeholders) ---
+
+// TODO: Implement logic for fetching wallet balance (HTTPS outcall to Sui network)
+#[ic_cdk::query]
+fn get_sui_wallet_balance(wallet_address: String) -> Result<u64, String> {
+    // Placeholder
+    Ok(0)
+}
+
+// TODO: Implement ZK proof verification logic
+#[ic_cdk::update]
+fn verify_zk_proof(/* params */) -> Result<bool, String> {
+    // Placeholder
+    Ok(true)
+}
+
+// --- Private Helper Functions ---
+
+fn is_admin(principal: Principal) -> bool {
+    USERS.with(|users| {
+        if let Some(user) = users.borrow().get(&principal) {
+            user.role == "admin"
+        } else {
+            false
+        }
+    })
+}
+
+// Loads the caller's metrics row for the day containing `now`, creating an empty one if needed.
+fn daily_metrics(user: Principal, now: u64) -> LearningMetrics {
+    let day = day_index(now);
+    LEARNING_METRICS.with(|metrics| metrics.borrow().get(&(user, day)))
+        .unwrap_or_else(|| LearningMetrics {
+            id: next_id("learning_metrics"),
+            user_id: user,
+            session_ids: vec![],
+            date: day * NANOS_PER_DAY,
+            time_spent_minutes: 0,
+            messages_sent: 0,
+            modules_completed: 0,
+            comprehension_scores: vec![],
+            difficulty_adjustments: vec![],
+            engagement_metrics: EngagementMetrics {
+                activity_count: 0,
+                first_activity_at: now,
+                last_activity_at: now,
+            },
+        })
+}
+
+// The learner's level for a topic, falling back to their global difficulty setting.
+fn effective_difficulty(user: Principal, topic: &str) -> String {
+    DIFFICULTY_PROFILES.with(|profiles| profiles.borrow().get(&user))
+        .and_then(|profile| profile.topics.into_iter().find(|t| t.topic == topic))
+        .map(|t| t.level)
+        .unwrap_or_else(|| default_difficulty(user))
+}
+
+fn default_difficulty(user: Principal) -> String {
+    USERS.with(|users| users.borrow().get(&user))
+        .map(|user| user.settings.difficulty_level)
+        .filter(|level| DIFFICULTY_LEVELS.contains(&level.as_str()))
+        .unwrap_or_else(|| "intermediate".to_string())
+}
+
+// Feeds a 0-100 score into the learner's per-topic window and moves them one
+// level up or down once the window average crosses a threshold. The window is
+// cleared after each change so the new level gets a fresh evaluation.
+fn apply_difficulty_signal(
+    user: Principal,
+    topic: &str,
+    score: f32,
+    source: &str,
+    now: u64,
+) -> (TopicDifficulty, Option<DifficultyAdjustment>) {
+    let mut profile = DIFFICULTY_PROFILES.with(|profiles| profiles.borrow().get(&user))
+        .unwrap_or_else(|| DifficultyProfile {
+            user_id: user,
+            topics: vec![],
+            updated_at: now,
+        });
+
+    let index = match profile.topics.iter().position(|t| t.topic == topic) {
+        Some(index) => index,
+        None => {
+            profile.topics.push(TopicDifficulty {
+                topic: topic.to_string(),
+                level: default_difficulty(user),
+                recent_scores: vec![],
+                updated_at: now,
+            });
+            profile.topics.len() - 1
+        }
+    };
+
+    let entry = &mut profile.topics[index];
+    entry.recent_scores.push(score);
+    if entry.recent_scores.len() > DIFFICULTY_WINDOW {
+        entry.recent_scores.remove(0);
+    }
+    entry.updated_at = now;
+
+    let mut adjustment = None;
+    if entry.recent_scores.len() >= DIFFICULTY_MIN_SAMPLES {
+        let samples = entry.recent_scores.len();
+        let average = entry.recent_scores.iter().sum::<f32>() / samples as f32;
+        let current = DIFFICULTY_LEVELS.iter().position(|l| *l == entry.level).unwrap_or(1);
+        let target = if average >= DIFFICULTY_PROMOTE_THRESHOLD && current + 1 < DIFFICULTY_LEVELS.len() {
+            Some(current + 1)
+        } else if average < DIFFICULTY_DEMOTE_THRESHOLD && current > 0 {
+            Some(current - 1)
+        } else {
+            None
+        };
+
+        if let Some(target) = target {
+            let record = DifficultyAdjustment {
+                id: next_id("difficulty_adjustment"),
+                topic: topic.to_string(),
+                from_level: entry.level.clone(),
+                to_level: DIFFICULTY_LEVELS[target].to_string(),
+                reason: format!(
+                    "Average score of {:.0} over the last {} results (latest from {}).",
+                    average, samples, source
+                ),
+                adjusted_at: now,
+            };
+            entry.level = record.to_level.clone();
+            entry.recent_scores.clear();
+            DIFFICULTY_ADJUSTMENTS.with(|adjustments| {
+                adjustments.borrow_mut().insert((user, record.id), record.clone());
+            });
+            adjustment = Some(record);
+        }
+    }
+
+    let difficulty = entry.clone();
+    profile.updated_at = now;
+    DIFFICULTY_PROFILES.with(|profiles| {
+        profiles.borrow_mut().insert(user, profile);
+    });
+
+    (difficulty, adjustment)
+}
+
+// Canonical key for a pair of users, independent of who initiated.
+fn pair_key(a: Principal, b: Principal) -> (Principal, Principal) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn index_user(user: &User) {
+    USERS_BY_USERNAME.with(|index| {
+        index.borrow_mut().insert((IndexTerm::new(&user.username.to_lowercase()), user.id), ());
+    });
+    USERS_BY_INTEREST.with(|index| {
+        let mut index = index.borrow_mut();
+        for interest in &user.settings.interests {
+            index.insert((IndexTerm::new(interest), user.id), ());
+        }
+    });
+}
+
+fn unindex_user(user: &User) {
+    USERS_BY_USERNAME.with(|index| {
+        index.borrow_mut().remove(&(IndexTerm::new(&user.username.to_lowercase()), user.id));
+    });
+    USERS_BY_INTEREST.with(|index| {
+        let mut index = index.borrow_mut();
+        for interest in &user.settings.interests {
+            index.remove(&(IndexTerm::new(interest), user.id));
+        }
+    });
+}
+
+fn backfill_discovery_indexes() {
+    if USERS_BY_USERNAME.with(|index| !index.borrow().is_empty()) {
+        return;
+    }
+    let users: Vec<User> = USERS.with(|users| users.borrow().iter().map(|(_, user)| user).collect());
+    for user in &users {
+        index_user(user);
+    }
+}
+
+// Builds the pair index from existing connections and requests the first time
+// a canister that predates it is upgraded.
+fn backfill_connection_pairs() {
+    if !CONNECTION_PAIRS.with(|pairs| pairs.borrow().is_empty()) {
+        return;
+    }
+    let mut pairs: HashMap<(Principal, Principal), ConnectionPair> = HashMap::new();
+    CONNECTIONS.with(|connections| {
+        for (id, conn) in connections.borrow().iter().filter(|(_, c)| c.status == "active") {
+            pairs.entry(pair_key(conn.user1_id, conn.user2_id)).or_default().connection_id = Some(id);
+        }
+    });
+    CONNECTION_REQUESTS.with(|requests| {
+        for (id, request) in requests.borrow().iter().filter(|(_, r)| r.status == "pending") {
+            let pair = pairs.entry(pair_key(request.sender_id, request.receiver_id)).or_default();
+            if pair.connection_id.is_none() {
+                pair.pending_request_id = Some(id);
+            }
+        }
+    });
+    CONNECTION_PAIRS.with(|store| {
+        let mut store = store.borrow_mut();
+        for (key, pair) in pairs {
+            store.insert(key, pair);
+        }
+    });
+}
+
+// Populates the per-user indexes from the primary tables the first time a
+// canister that predates them is upgraded.
+fn backfill_user_indexes() {
+    if REQUESTS_BY_SENDER.with(|index| index.borrow().is_empty()) {
+        let requests: Vec<ConnectionRequest> = CONNECTION_REQUESTS.with(|requests| {
+            requests.borrow().iter().map(|(_, request)| request).collect()
+        });
+        for request in &requests {
+            index_request(request);
+        }
+    }
+    if CONNECTIONS_BY_USER.with(|index| index.borrow().is_empty()) {
+        let connections: Vec<UserConnection> = CONNECTIONS.with(|connections| {
+            connections.borrow().iter().map(|(_, conn)| conn).filter(|conn| conn.status == "active").collect()
+        });
+        CONNECTIONS_BY_USER.with(|index| {
+            let mut index = index.borrow_mut();
+            for conn in connections {
+                index.insert((conn.user1_id, conn.id), ());
+                index.insert((conn.user2_id, conn.id), ());
+            }
+        });
+    }
+    if TUTORS_BY_USER.with(|index| index.borrow().is_empty()) {
+        let tutors: Vec<Tutor> = TUTORS.with(|tutors| tutors.borrow().iter().map(|(_, t)| t).collect());
+        TUTORS_BY_USER.with(|index| {
+            let mut index = index.borrow_mut();
+            for tutor in tutors {
+                index.insert((tutor.user_id, tutor.id), ());
+            }
+        });
+    }
+    if MEMBERSHIPS_BY_USER.with(|index| index.borrow().is_empty()) {
+        let memberships: Vec<GroupMembership> = GROUP_MEMBERSHIPS.with(|memberships| {
+            memberships.borrow().iter().map(|(_, m)| m).collect()
+        });
+        // Later rows win, so duplicate memberships resolve to the most recent one.
+        for membership in memberships {
+            MEMBERSHIPS_BY_USER.with(|index| {
+                index.borrow_mut().insert((membership.user_id, membership.group_id), membership.id);
+            });
+            MEMBERSHIPS_BY_GROUP.with(|index| {
+                index.borrow_mut().insert((membership.group_id, membership.user_id), membership.id);
+            });
+        }
+    }
+    if TASK_COMPLETIONS_BY_USER.with(|index| index.borrow().is_empty()) {
+        let completions: Vec<UserTaskCompletion> = USER_TASK_COMPLETIONS.with(|completions| {
+            completions.borrow().iter().map(|(_, c)| c).collect()
+        });
+        TASK_COMPLETIONS_BY_USER.with(|index| {
+            let mut index = index.borrow_mut();
+            for completion in completions {
+                index.insert((completion.user_id, completion.id), ());
+            }
+        });
+    }
+    if TUTOR_SESSIONS_BY_USER.with(|index| index.borrow().is_empty()) {
+        let sessions: Vec<TutorSession> = TUTOR_SESSIONS.with(|sessions| {
+            sessions.borrow().iter().map(|(_, s)| s).collect()
+        });
+        TUTOR_SESSIONS_BY_USER.with(|index| {
+            let mut index = index.borrow_mut();
+            for session in sessions {
+                index.insert((session.user_id, session.id), ());
+            }
+        });
+    }
+}
+
+// Marks a pending request as accepted and creates the connection for the pair.
+fn accept_request(request: ConnectionRequest) -> (ConnectionRequest, UserConnection) {
+    let now = ic_cdk::api::time();
+    let updated_request = ConnectionRequest {
+        status: "accepted".to_string(),
+        updated_at: now,
+        responded_at: Some(now),
+        ..request
+    };
+    CONNECTION_REQUESTS.with(|requests| {
+        requests.borrow_mut().insert(updated_request.id, updated_request.clone());
+    });
+    PENDING_REQUESTS_BY_AGE.with(|index| index.borrow_mut().remove(&(updated_request.created_at, updated_request.id)));
+
+    let connection_id = next_id("connection");
+    let new_connection = UserConnection {
+        id: connection_id,
+        user1_id: updated_request.sender_id,
+        user2_id: updated_request.receiver_id,
+        status: "active".to_string(),
+        created_at: now,
+        updated_at: now,
+    };
+    CONNECTIONS.with(|connections| {
+        connections.borrow_mut().insert(connection_id, new_connection.clone());
+    });
+    CONNECTIONS_BY_USER.with(|index| {
+        let mut index = index.borrow_mut();
+        index.insert((new_connection.user1_id, connection_id), ());
+        index.insert((new_connection.user2_id, connection_id), ());
+    });
+
+    CONNECTION_PAIRS.with(|pairs| {
+        pairs.borrow_mut().insert(
+            pair_key(updated_request.sender_id, updated_request.receiver_id),
+            ConnectionPair {
+                connection_id: Some(connection_id),
+                pending_request_id: None,
+            },
+        );
+    });
+
+    (updated_request, new_connection)
+}
+
+// Deletes a connection and frees the pair so the users can reconnect later.
+// Keeps the connection as "ended" for history and drops it from the indexes.
+fn end_connection(connection: UserConnection) {
+    let ended = UserConnection {
+        status: "ended".to_string(),
+        updated_at: ic_cdk::api::time(),
+        ..connection.clone()
+    };
+    CONNECTIONS.with(|connections| connections.borrow_mut().insert(connection.id, ended));
+    CONNECTIONS_BY_USER.with(|index| {
+        let mut index = index.borrow_mut();
+        index.remove(&(connection.user1_id, connection.id));
+        index.remove(&(connection.user2_id, connection.id));
+    });
+
+    let key = pair_key(connection.user1_id, connection.user2_id);
+    CONNECTION_PAIRS.with(|pairs| {
+        let mut pairs = pairs.borrow_mut();
+        if let Some(mut pair) = pairs.get(&key) {
+            pair.connection_id = None;
+            if pair.pending_request_id.is_none() {
+                pairs.remove(&key);
+            } else {
+                pairs.insert(key, pair);
+            }
+        }
+    });
+}
+
+fn validate_message_content(content: String) -> Result<String, String> {
+    let content = content.trim().to_string();
+    if content.is_empty() {
+        return Err("Message cannot be empty.".to_string());
+    }
+    if content.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err(format!("Message cannot exceed {} characters.", MAX_MESSAGE_LENGTH));
+    }
+    Ok(content)
+}
+
+fn participant_conversation(conversation_id: u64, user: Principal) -> Result<Conversation, String> {
+    let conversation = CONVERSATIONS.with(|conversations| conversations.borrow().get(&conversation_id))
+        .ok_or("Conversation not found.".to_string())?;
+    if conversation.user1_id != user && conversation.user2_id != user {
+        return Err("Conversation not found.".to_string());
+    }
+    Ok(conversation)
+}
+
+// Messages from the other participant after the user's last-read marker.
+fn unread_count(conversation: &Conversation, user: Principal) -> u64 {
+    let last_read = if conversation.user1_id == user {
+        conversation.user1_last_read_seq
+    } else {
+        conversation.user2_last_read_seq
+    };
+    DIRECT_MESSAGES.with(|messages| {
+        messages
+            .borrow()
+            .range((conversation.id, last_read + 1)..=(conversation.id, u64::MAX))
+            .filter(|(_, message)| message.sender_id != user && !message.is_deleted)
+            .count() as u64
+    })
+}
+
+// True if either user has blocked the other.
+fn is_blocked_between(a: Principal, b: Principal) -> bool {
+    BLOCKS.with(|blocks| {
+        let blocks = blocks.borrow();
+        blocks.contains_key(&(a, b)) || blocks.contains_key(&(b, a))
+    })
+}
+
+fn is_connected(a: Principal, b: Principal) -> bool {
+    CONNECTION_PAIRS.with(|pairs| pairs.borrow().get(&pair_key(a, b)))
+        .is_some_and(|pair| pair.connection_id.is_some())
+}
+
+fn can_view_profile(viewer: Principal, user: &User) -> bool {
+    if viewer == user.id {
+        return true;
+    }
+    if is_blocked_between(viewer, user.id) {
+        return false;
+    }
+    match user.settings.profile_visibility.as_str() {
+        "public" => true,
+        "connections" => is_connected(viewer, user.id),
+        _ => false,
+    }
+}
+
+// Who may see a user's feed events, per their `activity_sharing` setting:
+// "public" shares with everyone, "followers" with followers and connections,
+// "connections" with connections only; anything else keeps activity private.
+fn can_view_activity(viewer: Principal, actor: Principal) -> bool {
+    if viewer == actor {
+        return true;
+    }
+    if is_blocked_between(viewer, actor) {
+        return false;
+    }
+    let sharing = match USERS.with(|users| users.borrow().get(&actor)) {
+        Some(user) => user.settings.activity_sharing,
+        None => return false,
+    };
+    match sharing.as_str() {
+        "public" => true,
+        "followers" => {
+            is_connected(viewer, actor)
+                || FOLLOWS.with(|follows| follows.borrow().contains_key(&(viewer, actor)))
+        }
+        "connections" => is_connected(viewer, actor),
+        _ => false,
+    }
+}
+
+fn record_event(actor: Principal, event_type: &str, subject_id: u64, group_id: Option<u64>, title: String) -> ActivityEvent {
+    let event_id = next_id("activity_event");
+    let event = ActivityEvent {
+        id: event_id,
+        actor_id: actor,
+        event_type: event_type.to_string(),
+        subject_id,
+        group_id,
+        title,
+        created_at: ic_cdk::api::time(),
+    };
+    ACTIVITY_EVENTS.with(|events| {
+        events.borrow_mut().insert(event_id, event.clone());
+    });
+    EVENTS_BY_ACTOR.with(|index| {
+        index.borrow_mut().insert((actor, event_id), ());
+    });
+    if let Some(group_id) = group_id {
+        EVENTS_BY_GROUP.with(|index| {
+            index.borrow_mut().insert((group_id, event_id), ());
+        });
+        refresh_group_stats(group_id, Some(event.created_at));
+    }
+    event
+}
+
+fn feed_page(viewer: Principal, cursor: Option<u64>, limit: u64) -> FeedPage {
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+    let before = cursor.unwrap_or(u64::MAX);
+
+    let mut pages = vec![];
+    for user in connected_users(viewer).into_iter().filter(|user| can_view_activity(viewer, *user)) {
+        pages.push(actor_events(user, before, limit + 1, |event| event_group_visible(viewer, event)));
+    }
+    for group_id in active_group_ids(viewer) {
+        pages.push(group_events(group_id, viewer, before, limit + 1));
+    }
+    merge_feed_pages(pages, limit)
+}
+
+// Events that happened inside a group carry its name or its content's titles,
+// so they are only shown to viewers who may read the group.
+fn event_group_visible(viewer: Principal, event: &ActivityEvent) -> bool {
+    event.group_id.is_none_or(|group_id| viewable_group(group_id, viewer).is_ok())
+}
+
+// Merges newest-first pages from several feed sources, each read with one
+// event more than `limit` and paired with the id its scan stopped at, if any.
+fn merge_feed_pages(pages: Vec<(Vec<ActivityEvent>, Option<u64>)>, limit: usize) -> FeedPage {
+    // Every source has been read down to `floor`, so events from `floor` up are
+    // complete; anything older waits for the next page.
+    let mut floor = 0;
+    let mut events: Vec<ActivityEvent> = vec![];
+    for (page, resume_at) in pages {
+        floor = floor.max(resume_at.unwrap_or(0));
+        events.extend(page);
+    }
+    events.sort_by_key(|event| std::cmp::Reverse(event.id));
+    events.dedup_by_key(|event| event.id);
+    events.retain(|event| event.id >= floor);
+
+    let next_cursor = if events.len() > limit {
+        events.truncate(limit);
+        events.last().map(|event| event.id)
+    } else if floor > 0 {
+        Some(floor)
+    } else {
+        None
+    };
+
+    FeedPage { events, next_cursor }
+}
+
+// Up to `limit` of the actor's events with ids below `before` that pass `keep`,
+// newest first. Scans are bounded the same way as in `group_events`.
+fn actor_events(
+    actor: Principal,
+    before: u64,
+    limit: usize,
+    keep: impl Fn(&ActivityEvent) -> bool,
+) -> (Vec<ActivityEvent>, Option<u64>) {
+    let mut kept = vec![];
+    let mut resume_at = None;
+    EVENTS_BY_ACTOR.with(|index| {
+        ACTIVITY_EVENTS.with(|events| {
+            let events = events.borrow();
+            let index = index.borrow();
+            let ids = index.range((actor, 0)..(actor, before)).rev().map(|((_, id), _)| id);
+            for (examined, id) in ids.enumerate() {
+                if let Some(event) = events.get(&id).filter(|event| keep(event)) {
+                    kept.push(event);
+                }
+                if kept.len() == limit || examined + 1 == FEED_SCAN_WINDOW {
+                    resume_at = Some(id);
+                    break;
+                }
+            }
+        })
+    });
+    (kept, resume_at)
+}
+
+// Tutors can be made private again after they were announced, so their
+// announcements are only shown while they stay public.
+fn is_follow_feed_event(event: &ActivityEvent) -> bool {
+    if !FOLLOW_FEED_EVENT_TYPES.contains(&event.event_type.as_str()) {
+        return false;
+    }
+    event.event_type != "tutor_published"
+        || TUTORS.with(|tutors| tutors.borrow().get(&event.subject_id)).is_some_and(|tutor| tutor.is_public)
+}
+
+// Up to `limit` events in the group with ids below `before` that the viewer may see,
+// newest first. The viewer's own events are left out. At most
+// FEED_SCAN_WINDOW events are examined per call; if the scan stops
+// before the start of the group's history, the smallest id examined is
+// returned so the caller can resume below it.
+fn group_events(group_id: u64, viewer: Principal, before: u64, limit: usize) -> (Vec<ActivityEvent>, Option<u64>) {
+    let mut visible = vec![];
+    let mut resume_at = None;
+    EVENTS_BY_GROUP.with(|index| {
+        ACTIVITY_EVENTS.with(|events| {
+            let events = events.borrow();
+            let index = index.borrow();
+            let ids = index.range((group_id, 0)..(group_id, before)).rev().map(|((_, id), _)| id);
+            for (examined, id) in ids.enumerate() {
+                if let Some(event) = events.get(&id) {
+                    if event.actor_id != viewer && can_view_activity(viewer, event.actor_id) {
+                        visible.push(event);
+                    }
+                }
+                if visible.len() == limit || examined + 1 == FEED_SCAN_WINDOW {
+                    resume_at = Some(id);
+                    break;
+                }
+            }
+        })
+    });
+    (visible, resume_at)
+}
+
+type FollowIndex = RefCell<StableBTreeMap<(Principal, Principal), u64, Memory>>;
+
+fn follow_entries(index: &'static LocalKey<FollowIndex>, user: Principal) -> Vec<Principal> {
+    index.with(|index| {
+        index
+            .borrow()
+            .range((user, Principal::management_canister())..)
+            .take_while(|((owner, _), _)| *owner == user)
+            .map(|((_, other), _)| other)
+            .collect()
+    })
+}
+
+fn follow_stats(viewer: Principal, user: Principal) -> FollowStats {
+    FollowStats {
+        user_id: user,
+        followers: follow_entries(&FOLLOWERS, user).len() as u64,
+        following: follow_entries(&FOLLOWS, user).len() as u64,
+        is_following: FOLLOWS.with(|follows| follows.borrow().contains_key(&(viewer, user))),
+        follows_you: FOLLOWS.with(|follows| follows.borrow().contains_key(&(user, viewer))),
+    }
+}
+
+fn list_follows(
+    index: &'static LocalKey<FollowIndex>,
+    user_id: Principal,
+    offset: u64,
+    limit: u64,
+) -> Result<Vec<UserProfile>, String> {
+    let caller = ic_cdk::caller();
+    let user = USERS.with(|users| users.borrow().get(&user_id))
+        .ok_or("User not found.".to_string())?;
+    if !can_view_profile(caller, &user) {
+        return Err("User not found.".to_string());
+    }
+
+    Ok(follow_entries(index, user_id)
+        .into_iter()
+        .filter_map(|id| USERS.with(|users| users.borrow().get(&id)))
+        .filter(|user| can_view_profile(caller, user))
+        .skip(offset as usize)
+        .take(limit.min(MAX_PAGE_SIZE) as usize)
+        .map(UserProfile::from)
+        .collect())
+}
+
+// Returns false if `follower` was not following `followee`.
+fn unfollow(follower: Principal, followee: Principal) -> bool {
+    let removed = FOLLOWS.with(|follows| follows.borrow_mut().remove(&(follower, followee))).is_some();
+    FOLLOWERS.with(|followers| {
+        followers.borrow_mut().remove(&(followee, follower));
+    });
+    removed
+}
+
+// Moves a pending request to a terminal status and frees the pair for new requests.
+fn close_request(request: ConnectionRequest, status: &str) -> ConnectionRequest {
+    let now = ic_cdk::api::time();
+    let updated_request = ConnectionRequest {
+        status: status.to_string(),
+        updated_at: now,
+        responded_at: Some(now),
+        ..request
+    };
+    CONNECTION_REQUESTS.with(|requests| {
+        requests.borrow_mut().insert(updated_request.id, updated_request.clone());
+    });
+    PENDING_REQUESTS_BY_AGE.with(|index| index.borrow_mut().remove(&(updated_request.created_at, updated_request.id)));
+
+    let key = pair_key(updated_request.sender_id, updated_request.receiver_id);
+    CONNECTION_PAIRS.with(|pairs| {
+        let mut pairs = pairs.borrow_mut();
+        if let Some(mut pair) = pairs.get(&key) {
+            if pair.pending_request_id == Some(updated_request.id) {
+                pair.pending_request_id = None;
+                if pair.connection_id.is_none() {
+                    pairs.remove(&key);
+                } else {
+                    pairs.insert(key, pair);
+                }
+            }
+        }
+    });
+
+    updated_request
+}
+
+fn index_request(request: &ConnectionRequest) {
+    REQUESTS_BY_SENDER.with(|index| index.borrow_mut().insert((request.sender_id, request.id), ()));
+    REQUESTS_BY_RECEIVER.with(|index| index.borrow_mut().insert((request.receiver_id, request.id), ()));
+    if request.status == "pending" {
+        PENDING_REQUESTS_BY_AGE.with(|index| index.borrow_mut().insert((request.created_at, request.id), ()));
+    }
+}
+
+// Pages through the user's sent or received requests, newest first.
+fn list_requests(
+    index: &'static LocalKey<UserIndex>,
+    user: Principal,
+    status: Option<String>,
+    offset: u64,
+    limit: u64,
+) -> Vec<ConnectionRequest> {
+    index.with(|index| {
+        CONNECTION_REQUESTS.with(|requests| {
+            let requests = requests.borrow();
+            index
+                .borrow()
+                .range((user, 0)..=(user, u64::MAX))
+                .rev()
+                .filter_map(|((_, id), _)| requests.get(&id))
+                .filter(|request| status.as_ref().is_none_or(|s| &request.status == s))
+                .skip(offset as usize)
+                .take(limit.min(MAX_PAGE_SIZE) as usize)
+                .collect()
+        })
+    })
+}
+
+fn expire_connection_requests() {
+    let cutoff = ic_cdk::api::time().saturating_sub(CONNECTION_REQUEST_TTL);
+    let expired: Vec<u64> = PENDING_REQUESTS_BY_AGE.with(|index| {
+        index.borrow().range(..(cutoff, 0)).map(|((_, id), _)| id).collect()
+    });
+    for id in expired {
+        if let Some(request) = CONNECTION_REQUESTS.with(|requests| requests.borrow().get(&id)) {
+            close_request(request, "expired");
+        }
+    }
+}
+
+fn level_rank(level: &str) -> usize {
+    DIFFICULTY_LEVELS.iter().position(|l| *l == level).unwrap_or(1)
+}
+
+// Everyone the user shares an active connection with.
+fn connected_users(user: Principal) -> Vec<Principal> {
+    let ids = index_entries(&CONNECTIONS_BY_USER, user);
+    CONNECTIONS.with(|connections| {
+        let connections = connections.borrow();
+        ids.iter()
+            .filter_map(|id| connections.get(id))
+            .filter(|conn| conn.status == "active")
+            .map(|conn| if conn.user1_id == user { conn.user2_id } else { conn.user1_id })
+            .collect()
+    })
+}
+
+// Everyone the user shares an active study group membership with, excluding the user.
+fn group_mates(user: Principal) -> Vec<Principal> {
+    let mut mates: Vec<Principal> = active_group_ids(user)
+        .into_iter()
+        .flat_map(active_member_ids)
+        .filter(|member| *member != user)
+        .collect();
+    mates.sort();
+    mates.dedup();
+    mates
+}
+
+fn group_membership(group_id: u64, user: Principal) -> Option<GroupMembership> {
+    let membership_id = MEMBERSHIPS_BY_GROUP.with(|index| index.borrow().get(&(group_id, user)))?;
+    GROUP_MEMBERSHIPS.with(|memberships| memberships.borrow().get(&membership_id))
+}
+
+// Groups the user is an active member of.
+fn active_group_ids(user: Principal) -> Vec<u64> {
+    let membership_ids: Vec<u64> = MEMBERSHIPS_BY_USER.with(|index| {
+        index
+            .borrow()
+            .range((user, 0)..=(user, u64::MAX))
+            .map(|(_, membership_id)| membership_id)
+            .collect()
+    });
+    GROUP_MEMBERSHIPS.with(|memberships| {
+        let memberships = memberships.borrow();
+        membership_ids
+            .iter()
+            .filter_map(|id| memberships.get(id))
+            .filter(|m| m.status == "active")
+            .map(|m| m.group_id)
+            .collect()
+    })
+}
+
+fn active_member_ids(group_id: u64) -> Vec<Principal> {
+    active_memberships(group_id).into_iter().map(|m| m.user_id).collect()
+}
+
+fn active_memberships(group_id: u64) -> Vec<GroupMembership> {
+    group_memberships(group_id).into_iter().filter(|m| m.status == "active").collect()
+}
+
+// Every membership row of the group, whatever its status.
+fn group_memberships(group_id: u64) -> Vec<GroupMembership> {
+    let membership_ids: Vec<u64> = MEMBERSHIPS_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            // The management canister id is the empty principal, so it sorts first.
+            .range((group_id, Principal::management_canister())..)
+            .take_while(|((group, _), _)| *group == group_id)
+            .map(|(_, membership_id)| membership_id)
+            .collect()
+    });
+    GROUP_MEMBERSHIPS.with(|memberships| {
+        let memberships = memberships.borrow();
+        membership_ids
+            .iter()
+            .filter_map(|id| memberships.get(id))
+            .collect()
+    })
+}
+
+fn is_group_admin(group_id: u64, user: Principal) -> bool {
+    group_membership(group_id, user).is_some_and(|m| m.status == "active" && m.role == "admin")
+}
+
+fn active_membership(group_id: u64, user: Principal) -> Result<GroupMembership, String> {
+    group_membership(group_id, user)
+        .filter(|m| m.status == "active")
+        .ok_or("You are not a member of this group.".to_string())
+}
+
+// Messages are only ever soft-deleted, so the last key is the highest id ever used.
+fn last_group_message_id(group_id: u64) -> u64 {
+    GROUP_MESSAGES.with(|messages| {
+        messages
+            .borrow()
+            .range((group_id, 0)..=(group_id, u64::MAX))
+            .next_back()
+            .map(|((_, id), _)| id)
+            .unwrap_or(0)
+    })
+}
+
+// A poll that is still accepting votes. Polls past their expiry count as closed
+// even if the closing timer has not fired yet.
+fn open_poll(poll_id: u64) -> Result<GroupPoll, String> {
+    let poll = GROUP_POLLS.with(|polls| polls.borrow().get(&poll_id))
+        .ok_or("Poll not found.".to_string())?;
+    if !poll_is_open(&poll) {
+        return Err("This poll is closed.".to_string());
+    }
+    Ok(poll)
+}
+
+fn poll_is_open(poll: &GroupPoll) -> bool {
+    poll.is_active && poll.expires_at.is_none_or(|expires_at| expires_at > ic_cdk::api::time())
+}
+
+fn finish_poll(mut poll: GroupPoll) -> GroupPoll {
+    poll.is_active = false;
+    poll.closed_at = Some(ic_cdk::api::time());
+    GROUP_POLLS.with(|polls| {
+        polls.borrow_mut().insert(poll.id, poll.clone());
+    });
+    poll
+}
+
+fn schedule_poll_close(poll: &GroupPoll) {
+    if let Some(expires_at) = poll.expires_at {
+        let poll_id = poll.id;
+        let delay = expires_at.saturating_sub(ic_cdk::api::time());
+        ic_cdk_timers::set_timer(Duration::from_nanos(delay), move || {
+            if let Some(poll) = GROUP_POLLS.with(|polls| polls.borrow().get(&poll_id)) {
+                if poll.is_active {
+                    let poll = finish_poll(poll);
+                    log_group_activity(poll.group_id, ic_cdk::id(), "poll_closed", Some(poll_id), None, None);
+                }
+            }
+        });
+    }
+}
+
+type PollVoteKey = (u64, Principal, u64);
+
+fn user_poll_votes(votes: &StableBTreeMap<PollVoteKey, PollVote, Memory>, poll_id: u64, user: Principal) -> Vec<PollVoteKey> {
+    votes
+        .range((poll_id, user, 0)..=(poll_id, user, u64::MAX))
+        .map(|(key, _)| key)
+        .collect()
+}
+
+fn poll_results(poll: GroupPoll, viewer: Principal) -> PollResults {
+    let options: Vec<PollOption> = POLL_OPTIONS.with(|options| {
+        options
+            .borrow()
+            .range((poll.id, 0)..=(poll.id, u64::MAX))
+            .map(|(_, option)| option)
+            .collect()
+    });
+    let votes: Vec<PollVote> = POLL_VOTES.with(|votes| {
+        votes
+            .borrow()
+            .range((poll.id, Principal::management_canister(), 0)..)
+            .take_while(|((poll_id, _, _), _)| *poll_id == poll.id)
+            .map(|(_, vote)| vote)
+            .collect()
+    });
+
+    let mut voters: Vec<Principal> = votes.iter().map(|vote| vote.user_id).collect();
+    voters.dedup();
+    let my_votes = votes.iter().filter(|v| v.user_id == viewer).map(|v| v.option_id).collect();
+    let results_hidden = poll.hide_results_until_closed && poll_is_open(&poll);
+
+    let options = options
+        .into_iter()
+        .map(|option| {
+            let option_votes: Vec<&PollVote> = votes.iter().filter(|v| v.option_id == option.id).collect();
+            let (votes, voters) = if results_hidden {
+                (0, None)
+            } else if poll.is_anonymous {
+                (option_votes.len() as u64, None)
+            } else {
+                (option_votes.len() as u64, Some(option_votes.iter().map(|v| v.user_id).collect()))
+            };
+            PollOptionResult { option, votes, voters }
+        })
+        .collect();
+
+    PollResults {
+        total_voters: voters.len() as u64,
+        poll,
+        options,
+        my_votes,
+        results_hidden,
+    }
+}
+
+fn validate_new_session(session: NewStudySession) -> Result<NewStudySession, String> {
+    let title = session.title.trim().to_string();
+    if title.is_empty() {
+        return Err("Session title cannot be empty.".to_string());
+    }
+    if session.starts_at <= ic_cdk::api::time() {
+        return Err("Sessions must start in the future.".to_string());
+    }
+    if session.duration_minutes == 0 || session.duration_minutes > MAX_SESSION_MINUTES {
+        return Err(format!("Sessions must last between 1 and {} minutes.", MAX_SESSION_MINUTES));
+    }
+    if session.max_participants == 0 {
+        return Err("A session must allow at least one participant.".to_string());
+    }
+    let timezone = session.timezone.trim().to_string();
+    if timezone.is_empty() {
+        return Err("A timezone is required.".to_string());
+    }
+    // Offsets in use range from UTC-12:00 to UTC+14:00.
+    if !(-12 * 60..=14 * 60).contains(&session.utc_offset_minutes) {
+        return Err("UTC offset must be between -720 and 840 minutes.".to_string());
+    }
+    let mut topics: Vec<String> = session.topics.iter().filter_map(|t| normalize_topic(t)).collect();
+    topics.sort();
+    topics.dedup();
+
+    Ok(NewStudySession {
+        title,
+        description: session.description.and_then(non_empty),
+        timezone,
+        topics,
+        ..session
+    })
+}
+
+fn session_end(session: &StudySession) -> u64 {
+    session.starts_at + session.duration_minutes as u64 * NANOS_PER_MINUTE
+}
+
+fn store_session(session: &StudySession) {
+    SESSIONS_BY_GROUP.with(|index| {
+        index.borrow_mut().insert((session.group_id, session.starts_at, session.id), ());
+    });
+    STUDY_SESSIONS.with(|sessions| {
+        sessions.borrow_mut().insert(session.id, session.clone());
+    });
+}
+
+// Moves a session to a new start time, keeping the time-ordered indexes in step.
+fn move_session(session: &mut StudySession, new_start: u64) {
+    let old_start = session.starts_at;
+    SESSIONS_BY_GROUP.with(|index| {
+        let mut index = index.borrow_mut();
+        index.remove(&(session.group_id, old_start, session.id));
+        index.insert((session.group_id, new_start, session.id), ());
+    });
+    let users = session_participants(session.id)
+        .into_iter()
+        .map(|participant| participant.user_id)
+        .chain(session_attendance(session.id).into_iter().map(|attendance| attendance.user_id));
+    SESSIONS_BY_USER.with(|index| {
+        let mut index = index.borrow_mut();
+        for user in users {
+            index.remove(&(user, old_start, session.id));
+            index.insert((user, new_start, session.id), ());
+        }
+    });
+    session.starts_at = new_start;
+}
+
+fn validate_session_update(update: StudySessionUpdate) -> Result<StudySessionUpdate, String> {
+    let title = match update.title {
+        Some(title) => {
+            let title = title.trim().to_string();
+            if title.is_empty() {
+                return Err("Session title cannot be empty.".to_string());
+            }
+            Some(title)
+        }
+        None => None,
+    };
+    if update.starts_at.is_some_and(|start| start <= ic_cdk::api::time()) {
+        return Err("Sessions must start in the future.".to_string());
+    }
+    if update.duration_minutes.is_some_and(|d| d == 0 || d > MAX_SESSION_MINUTES) {
+        return Err(format!("Sessions must last between 1 and {} minutes.", MAX_SESSION_MINUTES));
+    }
+    if update.max_participants == Some(0) {
+        return Err("A session must allow at least one participant.".to_string());
+    }
+    let topics = update.topics.map(|topics| {
+        let mut topics: Vec<String> = topics.iter().filter_map(|t| normalize_topic(t)).collect();
+        topics.sort();
+        topics.dedup();
+        topics
+    });
+    Ok(StudySessionUpdate { title, topics, ..update })
+}
+
+// Applies an update to one session. `move_by` shifts the session by a signed
+// offset and reassigns it to another series, for splitting a series.
+fn apply_session_update(
+    mut session: StudySession,
+    update: &StudySessionUpdate,
+    move_by: Option<(u64, i64)>,
+) -> Result<StudySession, String> {
+    if let Some(max_participants) = update.max_participants {
+        if max_participants < session.confirmed_count {
+            return Err(format!(
+                "max_participants cannot be below the number of confirmed participants ({}).",
+                session.confirmed_count
+            ));
+        }
+        session.max_participants = max_participants;
+        promote_waitlist(&mut session);
+    }
+    if let Some(title) = &update.title {
+        session.title = title.clone();
+    }
+    if let Some(description) = &update.description {
+        session.description = non_empty(description.clone());
+    }
+    if let Some(duration) = update.duration_minutes {
+        session.duration_minutes = duration;
+    }
+    if let Some(topics) = &update.topics {
+        session.topics = topics.clone();
+    }
+    if let Some(starts_at) = update.starts_at {
+        move_session(&mut session, starts_at);
+    }
+    if let Some((series_id, delta)) = move_by {
+        let new_start = (session.starts_at as i64 + delta) as u64;
+        move_session(&mut session, new_start);
+        if let (Some(old_series), Some(occurrence)) = (session.series_id, session.occurrence_start) {
+            let new_occurrence = (occurrence as i64 + delta) as u64;
+            SERIES_OCCURRENCES.with(|index| {
+                let mut index = index.borrow_mut();
+                index.remove(&(old_series, occurrence));
+                index.insert((series_id, new_occurrence), session.id);
+            });
+            session.series_id = Some(series_id);
+            session.occurrence_start = Some(new_occurrence);
+        }
+    }
+
+    session.updated_at = ic_cdk::api::time();
+    STUDY_SESSIONS.with(|sessions| {
+        sessions.borrow_mut().insert(session.id, session.clone());
+    });
+    Ok(session)
+}
+
+fn validate_recurrence_rule(rule: RecurrenceRule, first_start: u64, offset_minutes: i32) -> Result<RecurrenceRule, String> {
+    if rule.interval == 0 || rule.interval > MAX_RECURRENCE_INTERVAL {
+        return Err(format!("Interval must be between 1 and {}.", MAX_RECURRENCE_INTERVAL));
+    }
+    match rule.frequency.as_str() {
+        "weekly" => {
+            let mut weekdays = rule.weekdays;
+            if weekdays.iter().any(|day| *day > 6) {
+                return Err("Weekdays must be between 0 (Monday) and 6 (Sunday).".to_string());
+            }
+            if weekdays.is_empty() {
+                let (first_day, _) = calendar::local_day_and_time(first_start, offset_minutes);
+                weekdays.push(calendar::weekday(first_day));
+            }
+            weekdays.sort();
+            weekdays.dedup();
+            Ok(RecurrenceRule { weekdays, ..rule })
+        }
+        "monthly" => Ok(RecurrenceRule { weekdays: vec![], ..rule }),
+        _ => Err("Frequency must be \"weekly\" or \"monthly\".".to_string()),
+    }
+}
+
+// A short human-readable description, e.g. "Every 2 weeks on Mon, Thu".
+fn describe_recurrence(rule: &RecurrenceRule) -> String {
+    let days: Vec<&str> = rule.weekdays.iter().map(|d| calendar::WEEKDAY_NAMES[*d as usize]).collect();
+    match (rule.frequency.as_str(), rule.interval) {
+        ("weekly", 1) => format!("Weekly on {}", days.join(", ")),
+        ("weekly", n) => format!("Every {} weeks on {}", n, days.join(", ")),
+        (_, 1) => "Monthly".to_string(),
+        (_, n) => format!("Every {} months", n),
+    }
+}
+
+// Occurrence start times of a series in `[from, to]`, respecting its end date.
+fn series_occurrences(series: &SessionSeries, from: u64, to: u64) -> Vec<u64> {
+    let to = series.until.map_or(to, |until| to.min(until));
+    if from > to {
+        return vec![];
+    }
+    match series.rule.frequency.as_str() {
+        "weekly" => calendar::weekly_occurrences(
+            series.first_start,
+            series.utc_offset_minutes,
+            series.rule.interval,
+            &series.rule.weekdays,
+            from,
+            to,
+        ),
+        _ => calendar::monthly_occurrences(series.first_start, series.utc_offset_minutes, series.rule.interval, from, to),
+    }
+}
+
+// Creates sessions for occurrences after `generated_until` up to `horizon`.
+// Occurrences already generated, including skipped (cancelled) ones, are not recreated.
+fn generate_series_sessions(series: &mut SessionSeries, horizon: u64) {
+    let now = ic_cdk::api::time();
+    for start in series_occurrences(series, series.generated_until + 1, horizon) {
+        if SERIES_OCCURRENCES.with(|index| index.borrow().contains_key(&(series.id, start))) {
+            continue;
+        }
+        let session = StudySession {
+            id: next_id("study_session"),
+            group_id: series.group_id,
+            creator_id: series.creator_id,
+            title: series.title.clone(),
+            description: series.description.clone(),
+            starts_at: start,
+            timezone: series.timezone.clone(),
+            utc_offset_minutes: series.utc_offset_minutes,
+            duration_minutes: series.duration_minutes,
+            max_participants: series.max_participants,
+            topics: series.topics.clone(),
+            status: "scheduled".to_string(),
+            confirmed_count: 0,
+            created_at: now,
+            updated_at: now,
+            series_id: Some(series.id),
+            occurrence_start: Some(start),
+        };
+        store_session(&session);
+        SERIES_OCCURRENCES.with(|index| {
+            index.borrow_mut().insert((series.id, start), session.id);
+        });
+        if group_membership(series.group_id, series.creator_id).is_some_and(|m| m.status == "active") {
+            let _ = set_rsvp(session, series.creator_id, "confirmed");
+        }
+    }
+
+    series.generated_until = series.generated_until.max(horizon);
+    if series.until.is_some_and(|until| until <= series.generated_until) {
+        series.status = "ended".to_string();
+    }
+    SESSION_SERIES.with(|store| {
+        store.borrow_mut().insert(series.id, series.clone());
+    });
+    SERIES_BY_GROUP.with(|index| index.borrow_mut().insert((series.group_id, series.id), ()));
+}
+
+// Ends `series` just before `occurrence_start` and starts a new series from that
+// occurrence shifted by `delta`, with the same rule moved to the new weekdays.
+fn split_series(series: &mut SessionSeries, occurrence_start: u64, delta: i64) -> SessionSeries {
+    let new_first_start = (occurrence_start as i64 + delta) as u64;
+    let (old_day, _) = calendar::local_day_and_time(occurrence_start, series.utc_offset_minutes);
+    let (new_day, _) = calendar::local_day_and_time(new_first_start, series.utc_offset_minutes);
+    let day_shift = new_day - old_day;
+
+    let mut weekdays: Vec<u8> = series
+        .rule
+        .weekdays
+        .iter()
+        .map(|day| (*day as i64 + day_shift).rem_euclid(7) as u8)
+        .collect();
+    weekdays.sort();
+
+    let now = ic_cdk::api::time();
+    let new_series = SessionSeries {
+        id: next_id("session_series"),
+        first_start: new_first_start,
+        rule: RecurrenceRule { weekdays, ..series.rule.clone() },
+        generated_until: (series.generated_until as i64 + delta).max(new_first_start as i64 - 1) as u64,
+        status: "active".to_string(),
+        created_at: now,
+        updated_at: now,
+        ..series.clone()
+    };
+
+    series.until = Some(occurrence_start - 1);
+    if series.generated_until >= occurrence_start - 1 {
+        series.status = "ended".to_string();
+    }
+    series.updated_at = now;
+    SESSION_SERIES.with(|store| {
+        let mut store = store.borrow_mut();
+        store.insert(series.id, series.clone());
+        store.insert(new_series.id, new_series.clone());
+    });
+    SERIES_BY_GROUP.with(|index| index.borrow_mut().insert((new_series.group_id, new_series.id), ()));
+
+    new_series
+}
+
+// Ids of a series' occurrences starting at or after `from`, in order.
+fn series_session_ids(series_id: u64, from: u64) -> Vec<u64> {
+    SERIES_OCCURRENCES.with(|index| {
+        index
+            .borrow()
+            .range((series_id, from)..=(series_id, u64::MAX))
+            .map(|(_, session_id)| session_id)
+            .collect()
+    })
+}
+
+// Generates the next window of occurrences for every active series.
+fn extend_session_series() {
+    let horizon = ic_cdk::api::time() + SERIES_WINDOW;
+    let active: Vec<SessionSeries> = SESSION_SERIES.with(|series| {
+        series.borrow().iter().map(|(_, s)| s).filter(|s| s.status == "active").collect()
+    });
+    for mut series in active {
+        let archived = STUDY_GROUPS.with(|groups| groups.borrow().get(&series.group_id))
+            .is_none_or(|group| group.is_archived);
+        if !archived {
+            generate_series_sessions(&mut series, horizon);
+        }
+    }
+}
+
+fn session_participants(session_id: u64) -> Vec<SessionParticipant> {
+    SESSION_PARTICIPANTS.with(|participants| {
+        participants
+            .borrow()
+            .range((session_id, Principal::management_canister())..)
+            .take_while(|((id, _), _)| *id == session_id)
+            .map(|(_, participant)| participant)
+            .collect()
+    })
+}
+
+// Applies an RSVP and keeps the confirmed count and waitlist consistent.
+fn set_rsvp(mut session: StudySession, user: Principal, requested: &str) -> Result<StudySession, String> {
+    let now = ic_cdk::api::time();
+    let previous = SESSION_PARTICIPANTS.with(|participants| participants.borrow().get(&(session.id, user)));
+    let was_confirmed = previous.as_ref().is_some_and(|p| p.status == "confirmed");
+
+    let status = granted_rsvp_status(requested, was_confirmed, session.confirmed_count, session.max_participants);
+    if previous.as_ref().is_some_and(|p| p.status == status) {
+        return Err(format!("Your RSVP is already {}.", status));
+    }
+
+    let participant = match previous {
+        // Rejoining the waitlist puts the user at the back of the queue.
+        Some(previous) => SessionParticipant {
+            status: status.to_string(),
+            joined_at: if status == "waitlisted" { now } else { previous.joined_at },
+            ..previous
+        },
+        None => SessionParticipant {
+            id: next_id("session_participant"),
+            session_id: session.id,
+            user_id: user,
+            status: status.to_string(),
+            joined_at: now,
+        },
+    };
+    SESSION_PARTICIPANTS.with(|participants| {
+        participants.borrow_mut().insert((session.id, user), participant);
+    });
+    SESSIONS_BY_USER.with(|index| {
+        index.borrow_mut().insert((user, session.starts_at, session.id), ());
+    });
+
+    session.confirmed_count = confirmed_count_after(session.confirmed_count, was_confirmed, status);
+    if was_confirmed && status != "confirmed" {
+        promote_waitlist(&mut session);
+    }
+    session.updated_at = now;
+    STUDY_SESSIONS.with(|sessions| {
+        sessions.borrow_mut().insert(session.id, session.clone());
+    });
+
+    Ok(session)
+}
+
+// Confirms waitlisted users, earliest first, while seats are free.
+fn promote_waitlist(session: &mut StudySession) {
+    let promoted = waitlist_promotions(session_participants(session.id), session.confirmed_count, session.max_participants);
+    for participant in promoted {
+        session.confirmed_count += 1;
+        SESSION_PARTICIPANTS.with(|participants| {
+            participants.borrow_mut().insert(
+                (session.id, participant.user_id),
+                SessionParticipant { status: "confirmed".to_string(), ..participant },
+            );
+        });
+    }
+}
+
+// A confirmation becomes a waitlist spot once every seat is taken. Users who
+// already hold a seat keep it.
+fn granted_rsvp_status(requested: &str, was_confirmed: bool, confirmed_count: u32, max_participants: u32) -> &str {
+    if requested == "confirmed" && !was_confirmed && confirmed_count >= max_participants {
+        "waitlisted"
+    } else {
+        requested
+    }
+}
+
+fn confirmed_count_after(confirmed_count: u32, was_confirmed: bool, status: &str) -> u32 {
+    match (was_confirmed, status == "confirmed") {
+        (false, true) => confirmed_count + 1,
+        (true, false) => confirmed_count.saturating_sub(1),
+        _ => confirmed_count,
+    }
+}
+
+// The waitlisted participants that fit in the free seats, earliest first.
+fn waitlist_promotions(
+    mut participants: Vec<SessionParticipant>,
+    confirmed_count: u32,
+    max_participants: u32,
+) -> Vec<SessionParticipant> {
+    participants.retain(|p| p.status == "waitlisted");
+    participants.sort_by_key(|p| p.joined_at);
+    participants.truncate(max_participants.saturating_sub(confirmed_count) as usize);
+    participants
+}
+
+// Looks up a session the user may change: their own, or any in a group they moderate.
+fn managed_session(session_id: u64, user: Principal) -> Result<StudySession, String> {
+    let session = STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&session_id))
+        .ok_or("Session not found.".to_string())?;
+    writable_group(session.group_id)?;
+    let membership = active_membership(session.group_id, user)?;
+    if session.creator_id != user && role_rank(&membership.role) > role_rank("moderator") {
+        return Err("Only the organizer or a group moderator can change this session.".to_string());
+    }
+    Ok(session)
+}
+
+// Appends to the group's activity log and updates the per-day counters the
+// activity stats are read from.
+fn log_group_activity(
+    group_id: u64,
+    user: Principal,
+    activity_type: &str,
+    subject_id: Option<u64>,
+    target_user_id: Option<Principal>,
+    content: Option<String>,
+) {
+    let now = ic_cdk::api::time();
+    let activity = GroupActivity {
+        id: next_id("group_activity"),
+        group_id,
+        user_id: user,
+        activity_type: activity_type.to_string(),
+        content,
+        created_at: now,
+        subject_id,
+        target_user_id,
+    };
+    GROUP_ACTIVITIES.with(|activities| {
+        activities.borrow_mut().insert((group_id, activity.id), activity);
+    });
+
+    // Events the canister records on its own, such as polls closing on
+    // schedule, are attributed to it and do not count toward member activity.
+    if user == ic_cdk::id() {
+        return;
+    }
+    let day = day_index(now);
+    GROUP_ACTIVE_MEMBERS.with(|index| {
+        let mut index = index.borrow_mut();
+        let actions = index.get(&(group_id, day, user)).unwrap_or(0);
+        index.insert((group_id, day, user), actions + 1);
+    });
+    if activity_type == "message" {
+        GROUP_DAILY_MESSAGES.with(|counts| {
+            let mut counts = counts.borrow_mut();
+            let count = counts.get(&(group_id, day)).unwrap_or(0);
+            counts.insert((group_id, day), count + 1);
+        });
+    }
+}
+
+// Drops per-member activity days that have fallen out of the stats window.
+// Daily message counts are small and kept.
+fn prune_group_activity_days() {
+    let cutoff = day_index(ic_cdk::api::time()).saturating_sub(ACTIVITY_STATS_DAYS);
+    let expired: Vec<(u64, u64, Principal)> = GROUP_ACTIVE_MEMBERS.with(|index| {
+        index
+            .borrow()
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|(_, day, _)| *day <= cutoff)
+            .collect()
+    });
+    GROUP_ACTIVE_MEMBERS.with(|index| {
+        let mut index = index.borrow_mut();
+        for key in &expired {
+            index.remove(key);
+        }
+    });
+}
+
+fn session_attendance(session_id: u64) -> Vec<SessionAttendance> {
+    SESSION_ATTENDANCE.with(|attendance| {
+        attendance
+            .borrow()
+            .range((session_id, Principal::management_canister())..)
+            .take_while(|((id, _), _)| *id == session_id)
+            .map(|(_, attendance)| attendance)
+            .collect()
+    })
+}
+
+// Stores a check-in and credits it to the member: a contribution, activity
+// and points, with fewer points for walk-ins than for confirmed RSVPs.
+fn record_attendance(session: &StudySession, user: Principal, recorded_by: Principal) -> Result<SessionAttendance, String> {
+    if SESSION_ATTENDANCE.with(|attendance| attendance.borrow().contains_key(&(session.id, user))) {
+        return Err("Attendance has already been recorded.".to_string());
+    }
+    let membership = active_membership(session.group_id, user)?;
+
+    let now = ic_cdk::api::time();
+    let rsvp_status = SESSION_PARTICIPANTS.with(|participants| participants.borrow().get(&(session.id, user)))
+        .map(|rsvp| rsvp.status)
+        .unwrap_or_else(|| "none".to_string());
+    let attendance = SessionAttendance {
+        session_id: session.id,
+        user_id: user,
+        points_earned: if rsvp_status == "confirmed" { ATTENDANCE_POINTS } else { WALK_IN_POINTS },
+        rsvp_status,
+        checked_in_at: now,
+        recorded_by,
+    };
+    SESSION_ATTENDANCE.with(|store| {
+        store.borrow_mut().insert((session.id, user), attendance.clone());
+    });
+    // Walk-ins have no RSVP, so index the session for them here.
+    SESSIONS_BY_USER.with(|index| {
+        index.borrow_mut().insert((user, session.starts_at, session.id), ());
+    });
+
+    insert_membership(GroupMembership {
+        contributions: membership.contributions + 1,
+        last_active_at: Some(now),
+        ..membership
+    });
+    log_group_activity(session.group_id, user, "attended", Some(session.id), None, None);
+    record_event(user, "session_attended", session.id, Some(session.group_id), session.title.clone());
+
+    Ok(attendance)
+}
+
+fn group_attendance(group_id: u64) -> GroupAttendanceReport {
+    let now = ic_cdk::api::time();
+    let session_ids: Vec<u64> = SESSIONS_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, 0, 0)..=(group_id, now, u64::MAX))
+            .map(|((_, _, id), _)| id)
+            .collect()
+    });
+    let ended: Vec<StudySession> = session_ids
+        .into_iter()
+        .filter_map(|id| STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&id)))
+        .filter(|session| session.status == "scheduled" && session_end(session) <= now)
+        .collect();
+
+    let mut totals = AttendanceStats { sessions: ended.len() as u32, ..Default::default() };
+    let mut members: BTreeMap<Principal, AttendanceStats> = active_memberships(group_id)
+        .into_iter()
+        .map(|membership| (membership.user_id, AttendanceStats::default()))
+        .collect();
+
+    for session in &ended {
+        let confirmed: Vec<Principal> = session_participants(session.id)
+            .into_iter()
+            .filter(|p| p.status == "confirmed")
+            .map(|p| p.user_id)
+            .collect();
+        let attendance = session_attendance(session.id);
+
+        for user in &confirmed {
+            let attended = attendance.iter().any(|a| a.user_id == *user);
+            for stats in [Some(&mut totals), members.get_mut(user)].into_iter().flatten() {
+                stats.confirmed += 1;
+                if attended {
+                    stats.attended += 1;
+                } else {
+                    stats.no_shows += 1;
+                }
+            }
+        }
+        for check_in in &attendance {
+            let walk_in = !confirmed.contains(&check_in.user_id);
+            if let Some(stats) = members.get_mut(&check_in.user_id) {
+                stats.points_earned += check_in.points_earned;
+                if walk_in {
+                    stats.walk_ins += 1;
+                }
+            }
+            totals.points_earned += check_in.points_earned;
+            if walk_in {
+                totals.walk_ins += 1;
+            }
+        }
+    }
+
+    totals.attendance_rate = attendance_rate(&totals);
+    let members = members
+        .into_iter()
+        .map(|(user_id, mut stats)| {
+            stats.sessions = stats.confirmed + stats.walk_ins;
+            stats.attendance_rate = attendance_rate(&stats);
+            MemberAttendance { user_id, stats }
+        })
+        .collect();
+    GroupAttendanceReport { group_id, totals, members }
+}
+
+// One member's attendance in the group, read from their own sessions rather
+// than the whole group's.
+fn member_attendance(group_id: u64, user: Principal) -> AttendanceStats {
+    let now = ic_cdk::api::time();
+    let mut stats = AttendanceStats::default();
+    SESSIONS_BY_USER.with(|index| {
+        for ((_, _, session_id), _) in index.borrow().range((user, 0, 0)..=(user, now, u64::MAX)) {
+            let Some(session) = STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&session_id)) else {
+                continue;
+            };
+            if session.group_id != group_id || session.status != "scheduled" || session_end(&session) > now {
+                continue;
+            }
+            let confirmed = SESSION_PARTICIPANTS.with(|participants| participants.borrow().get(&(session_id, user)))
+                .is_some_and(|rsvp| rsvp.status == "confirmed");
+            let check_in = SESSION_ATTENDANCE.with(|attendance| attendance.borrow().get(&(session_id, user)));
+            if confirmed {
+                stats.confirmed += 1;
+                if check_in.is_some() {
+                    stats.attended += 1;
+                } else {
+                    stats.no_shows += 1;
+                }
+            }
+            if let Some(check_in) = check_in {
+                stats.points_earned += check_in.points_earned;
+                if !confirmed {
+                    stats.walk_ins += 1;
+                }
+            }
+        }
+    });
+    stats.sessions = stats.confirmed + stats.walk_ins;
+    stats.attendance_rate = attendance_rate(&stats);
+    stats
+}
+
+fn attendance_rate(stats: &AttendanceStats) -> f32 {
+    if stats.confirmed == 0 {
+        0.0
+    } else {
+        stats.attended as f32 / stats.confirmed as f32
+    }
+}
+
+fn delete_session(session: &StudySession) {
+    for attendance in session_attendance(session.id) {
+        SESSIONS_BY_USER.with(|index| {
+            index.borrow_mut().remove(&(attendance.user_id, session.starts_at, session.id));
+        });
+        SESSION_ATTENDANCE.with(|store| store.borrow_mut().remove(&(session.id, attendance.user_id)));
+    }
+    for participant in session_participants(session.id) {
+        SESSIONS_BY_USER.with(|index| {
+            index.borrow_mut().remove(&(participant.user_id, session.starts_at, session.id));
+        });
+        SESSION_PARTICIPANTS.with(|participants| {
+            participants.borrow_mut().remove(&(session.id, participant.user_id));
+        });
+    }
+    SESSIONS_BY_GROUP.with(|index| index.borrow_mut().remove(&(session.group_id, session.starts_at, session.id)));
+    STUDY_SESSIONS.with(|sessions| sessions.borrow_mut().remove(&session.id));
+}
+
+fn completed_path_modules(user: Principal, path_id: u64) -> Vec<u64> {
+    PATH_MODULE_COMPLETIONS.with(|completions| {
+        completions
+            .borrow()
+            .range((user, path_id, 0)..=(user, path_id, u64::MAX))
+            .map(|((_, _, module_id), _)| module_id)
+            .collect()
+    })
+}
+
+// Completes the path for the user and issues their certificate. The caller
+// stores `progress`.
+fn complete_learning_path(user: Principal, mut path: LearningPath, progress: &mut LearningPathProgress, now: u64) {
+    progress.completed_at = Some(now);
+    path.completion_count += 1;
+    let score = path_comprehension_score(user, &path, progress.enrolled_at);
+    issue_certificate(user, "learning_path", path.id, path.title.clone(), score);
+    record_event(user, "path_completed", path.id, None, path.title.clone());
+    LEARNING_PATHS.with(|paths| {
+        paths.borrow_mut().insert(path.id, path);
+    });
+}
+
+// Looks up a tutor session that belongs to the user.
+fn owned_tutor_session(session_id: u64, user: Principal) -> Result<TutorSession, String> {
+    TUTOR_SESSIONS.with(|sessions| sessions.borrow().get(&session_id))
+        .filter(|session| session.user_id == user)
+        .ok_or("Tutor session not found.".to_string())
+}
+
+// Completes the course for the learner and issues their certificate. The
+// caller stores `course`.
+fn complete_tutor_course(learner: Principal, course: &mut TutorCourse) {
+    course.completed_at = Some(ic_cdk::api::time());
+    issue_certificate(learner, "tutor_course", course.id, course.topic.clone(), None);
+}
+
+// Returns false if the user had no calendar token.
+fn revoke_calendar_token_for(user: Principal) -> bool {
+    match USER_CALENDAR_TOKENS.with(|tokens| tokens.borrow_mut().remove(&user)) {
+        Some(token) => {
+            CALENDAR_TOKENS.with(|tokens| tokens.borrow_mut().remove(&token));
+            true
+        }
+        None => false,
+    }
+}
+
+// Events for a user's calendar feed: sessions they responded to (except
+// declines), every occurrence of recurring sessions in their groups, and
+// deadlines of active tasks they have not completed. Only the earliest
+// MAX_CALENDAR_EVENTS are kept.
+fn calendar_events(user: Principal, now: u64) -> Vec<ical::Event> {
+    let from = now.saturating_sub(CALENDAR_HISTORY);
+
+    let mut session_ids: Vec<u64> = SESSIONS_BY_USER.with(|index| {
+        index
+            .borrow()
+            .range((user, from, 0)..=(user, u64::MAX, u64::MAX))
+            .take(MAX_CALENDAR_EVENTS)
+            .map(|((_, _, id), _)| id)
+            .collect()
+    });
+    for group_id in active_group_ids(user) {
+        for series_id in group_series_ids(group_id) {
+            session_ids.extend(series_session_ids(series_id, from));
+        }
+    }
+    session_ids.sort();
+    session_ids.dedup();
+
+    let mut events: Vec<ical::Event> = session_ids
+        .into_iter()
+        .filter_map(|id| STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&id)))
+        .filter(|session| session.starts_at >= from)
+        .filter(|session| {
+            SESSION_PARTICIPANTS.with(|participants| participants.borrow().get(&(session.id, user)))
+                .is_none_or(|rsvp| rsvp.status != "declined")
+        })
+        .map(|session| {
+            let group_name = STUDY_GROUPS.with(|groups| groups.borrow().get(&session.group_id))
+                .map(|group| group.name)
+                .unwrap_or_default();
+            let mut description = format!("Study group: {}", group_name);
+            if let Some(details) = &session.description {
+                description.push_str("\n\n");
+                description.push_str(details);
+            }
+            ical::Event {
+                uid: format!("session-{}@cogni", session.id),
+                summary: session.title.clone(),
+                description: Some(description),
+                start: session.starts_at,
+                end: Some(session_end(&session)),
+                cancelled: session.status == "cancelled",
+                last_modified: session.updated_at,
+            }
+        })
+        .collect();
+
+    let completed: HashSet<u64> = index_entries(&TASK_COMPLETIONS_BY_USER, user)
+        .into_iter()
+        .filter_map(|id| USER_TASK_COMPLETIONS.with(|completions| completions.borrow().get(&id)))
+        .map(|completion| completion.task_id)
+        .collect();
+    let deadlines: Vec<ical::Event> = TASKS_BY_EXPIRY.with(|index| {
+        index
+            .borrow()
+            .range((from, 0)..)
+            .filter(|((_, task_id), _)| !completed.contains(task_id))
+            .filter_map(|((expires_at, task_id), _)| {
+                let task = TASKS.with(|tasks| tasks.borrow().get(&task_id)).filter(|task| task.is_active)?;
+                Some(ical::Event {
+                    uid: format!("task-{}@cogni", task.id),
+                    summary: format!("Deadline: {}", task.title),
+                    description: Some(task.description),
+                    start: expires_at,
+                    end: None,
+                    cancelled: false,
+                    last_modified: task.created_at,
+                })
+            })
+            .take(MAX_CALENDAR_EVENTS)
+            .collect()
+    });
+    events.extend(deadlines);
+    events.sort_by_key(|event| event.start);
+    events.truncate(MAX_CALENDAR_EVENTS);
+    events
+}
+
+// Returns the group if the user may read its contents: anyone for public
+// groups, active members for private ones.
+fn viewable_group(group_id: u64, user: Principal) -> Result<StudyGroup, String> {
+    let group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))
+        .ok_or("Study group not found.".to_string())?;
+    if group.is_private && active_membership(group_id, user).is_err() {
+        return Err("Only members can view the contents of a private group.".to_string());
+    }
+    Ok(group)
+}
+
+// Looks up a resource the user may change: their own, or any in a group they moderate.
+fn managed_resource(resource_id: u64, user: Principal) -> Result<StudyResource, String> {
+    let resource = STUDY_RESOURCES.with(|resources| resources.borrow().get(&resource_id))
+        .ok_or("Resource not found.".to_string())?;
+    writable_group(resource.group_id)?;
+    let membership = active_membership(resource.group_id, user)?;
+    if resource.user_id != user && role_rank(&membership.role) > role_rank("moderator") {
+        return Err("Only the author or a group moderator can change this resource.".to_string());
+    }
+    Ok(resource)
+}
+
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, String> {
+    let mut tags: Vec<String> = tags.iter().filter_map(|tag| normalize_topic(tag)).collect();
+    tags.sort();
+    tags.dedup();
+    if tags.len() > MAX_RESOURCE_TAGS {
+        return Err(format!("A resource can have at most {} tags.", MAX_RESOURCE_TAGS));
+    }
+    Ok(tags)
+}
+
+fn update_resource_tags(resource: &StudyResource, old_tags: &[String]) {
+    RESOURCE_TAGS.with(|index| {
+        let mut index = index.borrow_mut();
+        for tag in old_tags {
+            index.remove(&(resource.group_id, IndexTerm::new(tag), resource.id));
+        }
+        for tag in &resource.tags {
+            index.insert((resource.group_id, IndexTerm::new(tag), resource.id), ());
+        }
+    });
+}
+
+fn delete_resource(resource: &StudyResource) {
+    RESOURCE_TAGS.with(|index| {
+        let mut index = index.borrow_mut();
+        for tag in &resource.tags {
+            index.remove(&(resource.group_id, IndexTerm::new(tag), resource.id));
+        }
+    });
+    if let Some(size) = resource.file_size {
+        add_group_file_bytes(resource.group_id, -(size as i64));
+    }
+    RESOURCE_FILES.with(|files| files.borrow_mut().remove(&resource.id));
+    RESOURCES_BY_GROUP.with(|index| index.borrow_mut().remove(&(resource.group_id, resource.id)));
+    STUDY_RESOURCES.with(|resources| resources.borrow_mut().remove(&resource.id));
+}
+
+// Total bytes of files uploaded to the group.
+fn group_file_bytes(group_id: u64) -> u64 {
+    GROUP_FILE_BYTES.with(|bytes| bytes.borrow().get(&group_id)).unwrap_or(0)
+}
+
+fn add_group_file_bytes(group_id: u64, delta: i64) {
+    let total = group_file_bytes(group_id).saturating_add_signed(delta);
+    GROUP_FILE_BYTES.with(|bytes| {
+        let mut bytes = bytes.borrow_mut();
+        if total == 0 {
+            bytes.remove(&group_id);
+        } else {
+            bytes.insert(group_id, total);
+        }
+    });
+}
+
+// Returns the group if it exists and is not archived.
+fn writable_group(group_id: u64) -> Result<StudyGroup, String> {
+    let group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))
+        .ok_or("Study group not found.".to_string())?;
+    if group.is_archived {
+        return Err("This study group is archived.".to_string());
+    }
+    Ok(group)
+}
+
+fn set_group_archived(group_id: u64, archived: bool) -> Result<StudyGroup, String> {
+    let caller = ic_cdk::caller();
+    let mut group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))
+        .ok_or("Study group not found.".to_string())?;
+    if !is_group_admin(group_id, caller) {
+        return Err("Only group admins can perform this action.".to_string());
+    }
+    if group.is_archived == archived {
+        let state = if archived { "archived" } else { "not archived" };
+        return Err(format!("This study group is already {}.", state));
+    }
+
+    let now = ic_cdk::api::time();
+    group.is_archived = archived;
+    group.archived_at = if archived { Some(now) } else { None };
+    group.updated_at = now;
+    STUDY_GROUPS.with(|groups| {
+        groups.borrow_mut().insert(group_id, group.clone());
+    });
+    log_group_activity(group_id, caller, if archived { "archived" } else { "unarchived" }, None, None, None);
+
+    Ok(group)
+}
+
+fn group_series_ids(group_id: u64) -> Vec<u64> {
+    SERIES_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, 0)..=(group_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+fn group_invite_tokens(group_id: u64) -> Vec<String> {
+    INVITES_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, IndexTerm::default())..)
+            .take_while(|((group, _), _)| *group == group_id)
+            .map(|((_, token), _)| token.as_str().to_string())
+            .collect()
+    })
+}
+
+// Removes everything stored under a group except the group record itself.
+fn delete_group_data(group: &StudyGroup) {
+    let group_id = group.id;
+
+    for membership in group_memberships(group_id) {
+        MEMBERSHIPS_BY_USER.with(|index| index.borrow_mut().remove(&(membership.user_id, group_id)));
+        MEMBERSHIPS_BY_GROUP.with(|index| index.borrow_mut().remove(&(group_id, membership.user_id)));
+        GROUP_MEMBERSHIPS.with(|memberships| memberships.borrow_mut().remove(&membership.id));
+    }
+
+    let pending: Vec<((u64, Principal), u64)> = PENDING_JOIN_REQUESTS.with(|pending| {
+        pending
+            .borrow()
+            .range((group_id, Principal::management_canister())..)
+            .take_while(|((group, _), _)| *group == group_id)
+            .collect()
+    });
+    for (key, request_id) in pending {
+        PENDING_JOIN_REQUESTS.with(|pending| pending.borrow_mut().remove(&key));
+        GROUP_JOIN_REQUESTS.with(|requests| requests.borrow_mut().remove(&request_id));
+    }
+
+    let message_ids: Vec<u64> = GROUP_MESSAGES.with(|messages| {
+        messages
+            .borrow()
+            .range((group_id, 0)..=(group_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    GROUP_MESSAGES.with(|messages| {
+        let mut messages = messages.borrow_mut();
+        for id in &message_ids {
+            messages.remove(&(group_id, *id));
+        }
+    });
+    let reply_keys: Vec<(u64, u64, u64)> = GROUP_MESSAGE_REPLIES.with(|replies| {
+        replies
+            .borrow()
+            .range((group_id, 0, 0)..=(group_id, u64::MAX, u64::MAX))
+            .map(|(key, _)| key)
+            .collect()
+    });
+    GROUP_MESSAGE_REPLIES.with(|replies| {
+        let mut replies = replies.borrow_mut();
+        for key in &reply_keys {
+            replies.remove(key);
+        }
+    });
+
+    let resources: Vec<StudyResource> = RESOURCES_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, 0)..=(group_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect::<Vec<u64>>()
+    })
+    .into_iter()
+    .filter_map(|id| STUDY_RESOURCES.with(|resources| resources.borrow().get(&id)))
+    .collect();
+    for resource in &resources {
+        delete_resource(resource);
+    }
+
+    let poll_ids: Vec<u64> = POLLS_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, 0)..=(group_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    for poll_id in poll_ids {
+        let option_keys: Vec<(u64, u64)> = POLL_OPTIONS.with(|options| {
+            options.borrow().range((poll_id, 0)..=(poll_id, u64::MAX)).map(|(key, _)| key).collect()
+        });
+        POLL_OPTIONS.with(|options| {
+            let mut options = options.borrow_mut();
+            for key in &option_keys {
+                options.remove(key);
+            }
+        });
+        let vote_keys: Vec<PollVoteKey> = POLL_VOTES.with(|votes| {
+            votes
+                .borrow()
+                .range((poll_id, Principal::management_canister(), 0)..)
+                .take_while(|((id, _, _), _)| *id == poll_id)
+                .map(|(key, _)| key)
+                .collect()
+        });
+        POLL_VOTES.with(|votes| {
+            let mut votes = votes.borrow_mut();
+            for key in &vote_keys {
+                votes.remove(key);
+            }
+        });
+        GROUP_POLLS.with(|polls| polls.borrow_mut().remove(&poll_id));
+        POLLS_BY_GROUP.with(|index| index.borrow_mut().remove(&(group_id, poll_id)));
+    }
+
+    let sessions: Vec<StudySession> = SESSIONS_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, 0, 0)..=(group_id, u64::MAX, u64::MAX))
+            .map(|((_, _, id), _)| id)
+            .collect::<Vec<u64>>()
+    })
+    .into_iter()
+    .filter_map(|id| STUDY_SESSIONS.with(|sessions| sessions.borrow().get(&id)))
+    .collect();
+    for session in &sessions {
+        delete_session(session);
+    }
+
+    let activity_keys: Vec<(u64, u64)> = GROUP_ACTIVITIES.with(|activities| {
+        activities.borrow().range((group_id, 0)..=(group_id, u64::MAX)).map(|(key, _)| key).collect()
+    });
+    GROUP_ACTIVITIES.with(|activities| {
+        let mut activities = activities.borrow_mut();
+        for key in &activity_keys {
+            activities.remove(key);
+        }
+    });
+    let active_keys: Vec<(u64, u64, Principal)> = GROUP_ACTIVE_MEMBERS.with(|index| {
+        index
+            .borrow()
+            .range((group_id, 0, Principal::management_canister())..)
+            .take_while(|((group, _, _), _)| *group == group_id)
+            .map(|(key, _)| key)
+            .collect()
+    });
+    GROUP_ACTIVE_MEMBERS.with(|index| {
+        let mut index = index.borrow_mut();
+        for key in &active_keys {
+            index.remove(key);
+        }
+    });
+    let message_days: Vec<(u64, u64)> = GROUP_DAILY_MESSAGES.with(|counts| {
+        counts.borrow().range((group_id, 0)..=(group_id, u64::MAX)).map(|(key, _)| key).collect()
+    });
+    GROUP_DAILY_MESSAGES.with(|counts| {
+        let mut counts = counts.borrow_mut();
+        for key in &message_days {
+            counts.remove(key);
+        }
+    });
+    for series_id in group_series_ids(group_id) {
+        let occurrence_keys: Vec<(u64, u64)> = SERIES_OCCURRENCES.with(|index| {
+            index
+                .borrow()
+                .range((series_id, 0)..=(series_id, u64::MAX))
+                .map(|(key, _)| key)
+                .collect()
+        });
+        SERIES_OCCURRENCES.with(|index| {
+            let mut index = index.borrow_mut();
+            for key in &occurrence_keys {
+                index.remove(key);
+            }
+        });
+        SESSION_SERIES.with(|series| series.borrow_mut().remove(&series_id));
+        SERIES_BY_GROUP.with(|index| index.borrow_mut().remove(&(group_id, series_id)));
+    }
+
+    for token in group_invite_tokens(group_id) {
+        GROUP_INVITES.with(|invites| invites.borrow_mut().remove(&token));
+        INVITES_BY_GROUP.with(|index| index.borrow_mut().remove(&(group_id, IndexTerm::new(&token))));
+    }
+
+    let event_ids: Vec<u64> = EVENTS_BY_GROUP.with(|index| {
+        index
+            .borrow()
+            .range((group_id, 0)..=(group_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    for event_id in event_ids {
+        EVENTS_BY_GROUP.with(|index| index.borrow_mut().remove(&(group_id, event_id)));
+        if let Some(event) = ACTIVITY_EVENTS.with(|events| events.borrow_mut().remove(&event_id)) {
+            EVENTS_BY_ACTOR.with(|index| index.borrow_mut().remove(&(event.actor_id, event_id)));
+        }
+    }
+
+    if let Some(topic_id) = group.topic_id {
+        TOPIC_GROUPS.with(|index| index.borrow_mut().remove(&(topic_id, group_id)));
+    }
+}
+
+// Recounts active members and, if given, moves the group's last activity time,
+// keeping the sort indexes in step.
+fn refresh_group_stats(group_id: u64, activity_at: Option<u64>) {
+    let previous = GROUP_STATS.with(|stats| stats.borrow().get(&group_id));
+    let last_activity_at = activity_at
+        .or(previous.as_ref().map(|s| s.last_activity_at))
+        .unwrap_or_else(ic_cdk::api::time);
+    let stats = StudyGroupStats {
+        member_count: active_memberships(group_id).len() as u32,
+        last_activity_at,
+    };
+
+    if let Some(previous) = previous {
+        GROUPS_BY_MEMBERS.with(|index| index.borrow_mut().remove(&(previous.member_count, group_id)));
+        GROUPS_BY_ACTIVITY.with(|index| index.borrow_mut().remove(&(previous.last_activity_at, group_id)));
+    }
+    GROUPS_BY_MEMBERS.with(|index| index.borrow_mut().insert((stats.member_count, group_id), ()));
+    GROUPS_BY_ACTIVITY.with(|index| index.borrow_mut().insert((stats.last_activity_at, group_id), ()));
+    GROUP_STATS.with(|store| store.borrow_mut().insert(group_id, stats));
+}
+
+// Runs `f` over group ids in listing order, read lazily from the index for the
+// given sort. Every group has a stats row, so "newest" walks those ids backwards.
+fn with_sorted_group_ids<R>(sort_by: &str, f: impl FnOnce(&mut dyn Iterator<Item = u64>) -> R) -> R {
+    match sort_by {
+        "members" => GROUPS_BY_MEMBERS.with(|index| f(&mut index.borrow().iter().rev().map(|((_, id), _)| id))),
+        "newest" => GROUP_STATS.with(|stats| f(&mut stats.borrow().iter().rev().map(|(id, _)| id))),
+        _ => GROUPS_BY_ACTIVITY.with(|index| f(&mut index.borrow().iter().rev().map(|((_, id), _)| id))),
+    }
+}
+
+// Listing order is descending by this key; the group id breaks ties.
+fn group_sort_key(summary: &StudyGroupSummary, sort_by: &str) -> (u64, u64) {
+    match sort_by {
+        "members" => (summary.member_count as u64, summary.group.id),
+        "newest" => (0, summary.group.id),
+        _ => (summary.last_activity_at, summary.group.id),
+    }
+}
+
+// The first `count` candidates in listing order. Only `count` summaries are
+// held at a time, however many candidates there are.
+fn top_groups(candidates: impl Iterator<Item = StudyGroupSummary>, sort_by: &str, count: usize) -> Vec<StudyGroupSummary> {
+    let mut smallest = BinaryHeap::new();
+    let mut kept: HashMap<u64, StudyGroupSummary> = HashMap::new();
+    for summary in candidates {
+        let key = group_sort_key(&summary, sort_by);
+        if smallest.len() == count {
+            match smallest.peek() {
+                Some(std::cmp::Reverse(min)) if key > *min => {
+                    if let Some(std::cmp::Reverse((_, evicted))) = smallest.pop() {
+                        kept.remove(&evicted);
+                    }
+                }
+                _ => continue,
+            }
+        }
+        smallest.push(std::cmp::Reverse(key));
+        kept.insert(summary.group.id, summary);
+    }
+
+    let mut summaries: Vec<StudyGroupSummary> = kept.into_values().collect();
+    summaries.sort_by_key(|summary| std::cmp::Reverse(group_sort_key(summary, sort_by)));
+    summaries
+}
+
+fn group_summary(group_id: u64) -> Option<StudyGroupSummary> {
+    let group = STUDY_GROUPS.with(|groups| groups.borrow().get(&group_id))?;
+    let stats = GROUP_STATS.with(|stats| stats.borrow().get(&group_id)).unwrap_or_default();
+    Some(StudyGroupSummary {
+        group,
+        member_count: stats.member_count,
+        last_activity_at: stats.last_activity_at,
+    })
+}
+
+fn matches_group_filter(summary: &StudyGroupSummary, filter: &StudyGroupFilter, query_words: &[String]) -> bool {
+    let group = &summary.group;
+    if group.is_archived {
+        return false;
+    }
+    if filter.learning_level.as_ref().is_some_and(|level| &group.learning_level != level) {
+        return false;
+    }
+    if filter.topic_id.is_some() && group.topic_id != filter.topic_id {
+        return false;
+    }
+    if filter.has_capacity && summary.member_count >= group.max_members {
+        return false;
+    }
+    if filter.public_only && group.is_private {
+        return false;
+    }
+    let group_words = group_keywords(group);
+    query_words
+        .iter()
+        .all(|word| group_words.iter().any(|keyword| keyword.starts_with(word.as_str())))
+}
+
+// Lowercased, de-duplicated words of at least two characters.
+fn keywords(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 2)
+        .map(|word| word.to_lowercase())
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+fn group_keywords(group: &StudyGroup) -> Vec<String> {
+    keywords(&format!("{} {}", group.name, group.description.as_deref().unwrap_or("")))
+}
+
+fn index_group(group: &StudyGroup) {
+    GROUPS_BY_LEVEL.with(|index| {
+        index.borrow_mut().insert((IndexTerm::new(&group.learning_level), group.id), ());
+    });
+    GROUP_KEYWORDS.with(|index| {
+        let mut index = index.borrow_mut();
+        for word in group_keywords(group) {
+            index.insert((IndexTerm::new(&word), group.id), ());
+        }
+    });
+}
+
+fn unindex_group(group: &StudyGroup) {
+    GROUPS_BY_LEVEL.with(|index| {
+        index.borrow_mut().remove(&(IndexTerm::new(&group.learning_level), group.id));
+    });
+    GROUP_KEYWORDS.with(|index| {
+        let mut index = index.borrow_mut();
+        for word in group_keywords(group) {
+            index.remove(&(IndexTerm::new(&word), group.id));
+        }
+    });
+}
+
+// Only for deleted groups; edits keep the group's stats and their indexes.
+fn remove_group_stats(group_id: u64) {
+    if let Some(stats) = GROUP_STATS.with(|stats| stats.borrow_mut().remove(&group_id)) {
+        GROUPS_BY_MEMBERS.with(|index| index.borrow_mut().remove(&(stats.member_count, group_id)));
+        GROUPS_BY_ACTIVITY.with(|index| index.borrow_mut().remove(&(stats.last_activity_at, group_id)));
+    }
+}
+
+fn backfill_group_indexes() {
+    if GROUP_STATS.with(|stats| !stats.borrow().is_empty()) {
+        return;
+    }
+    let groups: Vec<StudyGroup> = STUDY_GROUPS.with(|groups| groups.borrow().iter().map(|(_, group)| group).collect());
+    for group in &groups {
+        index_group(group);
+        refresh_group_stats(group.id, Some(group.updated_at));
+    }
+}
+
+fn validate_group_name(name: String) -> Result<String, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Group name cannot be empty.".to_string());
+    }
+    if name.chars().count() > MAX_GROUP_NAME_LENGTH {
+        return Err(format!("Group name cannot exceed {} characters.", MAX_GROUP_NAME_LENGTH));
+    }
+    Ok(name)
+}
+
+fn validate_learning_level(level: &str) -> Result<(), String> {
+    if !DIFFICULTY_LEVELS.contains(&level) {
+        return Err(format!("Learning level must be one of: {}.", DIFFICULTY_LEVELS.join(", ")));
+    }
+    Ok(())
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim().to_string();
+    if value.is_empty() { None } else { Some(value) }
+}
+
+// Lower ranks carry more authority.
+fn role_rank(role: &str) -> u8 {
+    match role {
+        "admin" => 0,
+        "moderator" => 1,
+        _ => 2,
+    }
+}
+
+// Looks up the membership an admin wants to change. The group creator can only
+// be changed by themselves, e.g. by leaving.
+fn moderated_membership(group_id: u64, admin: Principal, user_id: Principal) -> Result<GroupMembership, String> {
+    let group = writable_group(group_id)?;
+    if !is_group_admin(group_id, admin) {
+        return Err("Only group admins can manage members.".to_string());
+    }
+    if group.creator_id == user_id && admin != user_id {
+        return Err("The group owner cannot be changed by other admins.".to_string());
+    }
+    group_membership(group_id, user_id).ok_or("This user is not a member of the group.".to_string())
+}
+
+// A group must always keep at least one active admin.
+fn ensure_other_admin(group_id: u64, membership: &GroupMembership) -> Result<(), String> {
+    if membership.role != "admin" || membership.status != "active" {
+        return Ok(());
+    }
+    let other_admins = active_memberships(group_id)
+        .iter()
+        .filter(|m| m.role == "admin" && m.user_id != membership.user_id)
+        .count();
+    if other_admins == 0 {
+        return Err("A group must keep at least one admin. Promote another member first.".to_string());
+    }
+    Ok(())
+}
+
+// Stores a membership and keeps both membership indexes pointing at it.
+fn insert_membership(membership: GroupMembership) {
+    MEMBERSHIPS_BY_USER.with(|index| {
+        index.borrow_mut().insert((membership.user_id, membership.group_id), membership.id);
+    });
+    MEMBERSHIPS_BY_GROUP.with(|index| {
+        index.borrow_mut().insert((membership.group_id, membership.user_id), membership.id);
+    });
+    let group_id = membership.group_id;
+    GROUP_MEMBERSHIPS.with(|memberships| {
+        memberships.borrow_mut().insert(membership.id, membership);
+    });
+    refresh_group_stats(group_id, None);
+}
+
+// Rejects users who are already members, are banned, or would exceed the group's capacity.
+fn check_can_join(group: &StudyGroup, user: Principal) -> Result<(), String> {
+    if group.is_archived {
+        return Err("This study group is archived.".to_string());
+    }
+    match group_membership(group.id, user) {
+        Some(m) if m.status == "active" => return Err("You are already a member of this group.".to_string()),
+        Some(m) if m.status == "banned" => return Err("You are banned from this group.".to_string()),
+        _ => {}
+    }
+    if active_memberships(group.id).len() >= group.max_members as usize {
+        return Err("This study group is full.".to_string());
+    }
+    Ok(())
+}
+
+// Adds the user to the group as a member, reusing their old membership row if
+// they were a member before.
+fn admit_member(group: &StudyGroup, user: Principal) -> Result<GroupMembership, String> {
+    check_can_join(group, user)?;
+
+    let now = ic_cdk::api::time();
+    let membership = match group_membership(group.id, user) {
+        Some(previous) => GroupMembership {
+            role: "member".to_string(),
+            status: "active".to_string(),
+            joined_at: now,
+            last_active_at: Some(now),
+            ..previous
+        },
+        None => GroupMembership {
+            id: next_id("group_membership"),
+            user_id: user,
+            group_id: group.id,
+            role: "member".to_string(),
+            status: "active".to_string(),
+            joined_at: now,
+            contributions: 0,
+            last_active_at: Some(now),
+        },
+    };
+
+    insert_membership(membership.clone());
+    log_group_activity(group.id, user, "joined", None, None, None);
+    record_event(user, "group_joined", group.id, Some(group.id), group.name.clone());
+
+    Ok(membership)
+}
+
+fn pending_join_request(request_id: u64) -> Result<GroupJoinRequest, String> {
+    let request = GROUP_JOIN_REQUESTS.with(|requests| requests.borrow().get(&request_id))
+        .ok_or("Join request not found.".to_string())?;
+    if request.status != "pending" {
+        return Err("This join request has already been handled.".to_string());
+    }
+    Ok(request)
+}
+
+fn close_join_request(request: GroupJoinRequest, status: &str, responded_by: Option<Principal>) -> GroupJoinRequest {
+    let updated_request = GroupJoinRequest {
+        status: status.to_string(),
+        responded_at: Some(ic_cdk::api::time()),
+        responded_by,
+        ..request
+    };
+    GROUP_JOIN_REQUESTS.with(|requests| {
+        requests.borrow_mut().insert(updated_request.id, updated_request.clone());
+    });
+    PENDING_JOIN_REQUESTS.with(|pending| {
+        pending.borrow_mut().remove(&(updated_request.group_id, updated_request.user_id));
+    });
+    updated_request
+}
+
+type UserIndex = RefCell<StableBTreeMap<(Principal, u64), (), Memory>>;
+
+fn index_entries(index: &'static LocalKey<UserIndex>, user: Principal) -> Vec<u64> {
+    index.with(|index| {
+        index
+            .borrow()
+            .range((user, 0)..=(user, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    })
+}
+
+fn topic_subtree_ids(root: u64) -> Vec<u64> {
+    let all: Vec<(u64, Option<u64>)> = TOPICS.with(|topics| {
+        topics.borrow().iter().map(|(id, topic)| (id, topic.parent_id)).collect()
+    });
+    let mut subtree = vec![root];
+    let mut next = 0;
+    while next < subtree.len() {
+        let parent = subtree[next];
+        subtree.extend(all.iter().filter(|(_, p)| *p == Some(parent)).map(|(id, _)| *id));
+        next += 1;
+    }
+    subtree
+}
+
+fn validate_topic_ids(mut topic_ids: Vec<u64>) -> Result<Vec<u64>, String> {
+    topic_ids.sort();
+    topic_ids.dedup();
+    if topic_ids.iter().any(|id| !TOPICS.with(|topics| topics.borrow().contains_key(id))) {
+        return Err("Topic not found.".to_string());
+    }
+    Ok(topic_ids)
+}
+
+type TopicIndex = RefCell<StableBTreeMap<(u64, u64), (), Memory>>;
+
+fn topic_index_entries(index: &'static LocalKey<TopicIndex>, topic_id: u64) -> Vec<u64> {
+    index.with(|index| {
+        index
+            .borrow()
+            .range((topic_id, 0)..=(topic_id, u64::MAX))
+            .map(|((_, entity_id), _)| entity_id)
+            .collect()
+    })
+}
+
+fn update_topic_index(index: &'static LocalKey<TopicIndex>, entity_id: u64, old: &[u64], new: &[u64]) {
+    index.with(|index| {
+        let mut index = index.borrow_mut();
+        for topic_id in old.iter().filter(|t| !new.contains(t)) {
+            index.remove(&(*topic_id, entity_id));
+        }
+        for topic_id in new {
+            index.insert((*topic_id, entity_id), ());
+        }
+    });
+}
+
+// Average comprehension score on the path's tags since the learner enrolled, if any were recorded.
+fn path_comprehension_score(user: Principal, path: &LearningPath, since: u64) -> Option<f32> {
+    let scores: Vec<f32> = LEARNING_METRICS.with(|metrics| {
+        metrics
+            .borrow()
+            .range((user, day_index(since))..=(user, u64::MAX))
+            .flat_map(|(_, m)| m.comprehension_scores)
+            .filter(|s| s.recorded_at >= since && path.tags.contains(&s.topic))
+            .map(|s| s.score)
+            .collect()
+    });
+    if scores.is_empty() {
+        None
+    } else {
+        Some(scores.iter().sum::<f32>() / scores.len() as f32)
+    }
+}
+
+fn issue_certificate(user: Principal, kind: &str, subject_id: u64, title: String, score: Option<f32>) -> Certificate {
+    let certificate_id = next_id("certificate");
+    let certificate = Certificate {
+        id: certificate_id,
+        public_id: certificate_id.to_string(),
+        user_id: user,
+        kind: kind.to_string(),
+        subject_id,
+        title,
+        score,
+        issued_at: ic_cdk::api::time(),
+    };
+
+    let encoded = candid::encode_one(&certificate).expect("failed to encode certificate");
+    CERTIFICATES.with(|certificates| {
+        certificates.borrow_mut().insert(certificate_id, certificate.clone());
+    });
+    let leaf = certification::leaf_hash(&encoded);
+    CERTIFICATE_LEAVES.with(|leaves| {
+        leaves.borrow_mut().insert(certificate_id, leaf);
+    });
+    append_certificate_leaf(certificate_id, leaf);
+    CERTIFICATES_BY_USER.with(|index| {
+        index.borrow_mut().insert((user, certificate_id), ());
+    });
+    update_certified_root();
+
+    certificate
+}
+
+// Adds the leaf to the stored Merkle tree, rewriting only its path to the root.
+fn append_certificate_leaf(certificate_id: u64, leaf: certification::Hash) {
+    let position = CERTIFICATE_POSITIONS.with(|positions| positions.borrow().len());
+    let updates = CERTIFICATE_TREE.with(|tree| {
+        let tree = tree.borrow();
+        certification::append(leaf, position, |level, index| {
+            tree.get(&(level, index)).expect("missing Merkle tree node")
+        })
+    });
+    CERTIFICATE_TREE.with(|tree| {
+        let mut tree = tree.borrow_mut();
+        for (level, index, hash) in updates {
+            tree.insert((level, index), hash);
+        }
+    });
+    CERTIFICATE_POSITIONS.with(|positions| {
+        positions.borrow_mut().insert(certificate_id, position);
+    });
+}
+
+fn certificate_root() -> certification::Hash {
+    let leaf_count = CERTIFICATE_POSITIONS.with(|positions| positions.borrow().len());
+    if leaf_count == 0 {
+        return certification::empty_root();
+    }
+    let level = certification::root_level(leaf_count);
+    CERTIFICATE_TREE.with(|tree| tree.borrow().get(&(level, 0))).expect("missing Merkle tree root")
+}
+
+fn update_certified_root() {
+    ic_cdk::api::set_certified_data(&certificate_root());
+}
+
+// Certificates issued before the tree was stored only have their leaf hash.
+// Rebuilds the tree from those leaves, in issuance order, on the first upgrade.
+fn backfill_certificate_tree() {
+    if CERTIFICATE_POSITIONS.with(|positions| !positions.borrow().is_empty()) {
+        return;
+    }
+    let leaves: Vec<(u64, certification::Hash)> = CERTIFICATE_LEAVES.with(|leaves| leaves.borrow().iter().collect());
+    for (certificate_id, leaf) in leaves {
+        append_certificate_leaf(certificate_id, leaf);
+    }
+}
+
+fn day_index(timestamp: u64) -> u64 {
+    timestamp / NANOS_PER_DAY
+}
+
+// Topics are free-form labels coming from the frontend; compare them case-insensitively.
+fn normalize_topic(topic: &str) -> Option<String> {
+    let topic = topic.trim().to_lowercase();
+    if topic.is_empty() {
+        None
+    } else {
+        Some(topic)
+    }
+}
+
+// --- Candid Generation ---
+ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn add_user(id: Principal, activity_sharing: &str) -> User {
+        let user = User {
+            id,
+            public_id: id.to_text(),
+            email: String::new(),
+            username: id.to_text(),
+            first_name: None,
+            last_name: None,
+            is_active: true,
+            is_verified: false,
+            created_at: 0,
+            updated_at: 0,
+            last_login: None,
+            oauth_provider: None,
+            oauth_id: None,
+            avatar_url: None,
+            bio: None,
+            blockchain_wallet_address: None,
+            blockchain_wallet_type: None,
+            blockchain_wallet_connected_at: None,
+            wallet_address: None,
+            public_key: None,
+            role: "user".to_string(),
+            status: "active".to_string(),
+            location: None,
+            subscription: "free".to_string(),
+            last_active: 0,
+            settings: UserSettings {
+                learning_style: String::new(),
+                preferred_language: String::new(),
+                difficulty_level: "beginner".to_string(),
+                daily_goal_hours: 1,
+                two_factor_enabled: false,
+                font_size: String::new(),
+                contrast: String::new(),
+                ai_interaction_style: String::new(),
+                profile_visibility: "public".to_string(),
+                activity_sharing: activity_sharing.to_string(),
+                interests: vec![],
+            },
+        };
+        USERS.with(|users| users.borrow_mut().insert(id, user.clone()));
+        user
+    }
+
+    fn connect(id: u64, a: Principal, b: Principal) {
+        let connection = UserConnection {
+            id,
+            user1_id: a,
+            user2_id: b,
+            status: "active".to_string(),
+            created_at: 0,
+            updated_at: 0,
+        };
+        CONNECTIONS.with(|connections| connections.borrow_mut().insert(id, connection));
+        CONNECTIONS_BY_USER.with(|index| {
+            let mut index = index.borrow_mut();
+            index.insert((a, id), ());
+            index.insert((b, id), ());
+        });
+        CONNECTION_PAIRS.with(|pairs| {
+            pairs.borrow_mut().insert(pair_key(a, b), ConnectionPair { connection_id: Some(id), pending_request_id: None });
+        });
+    }
+
+    fn add_group(id: u64, is_private: bool) -> StudyGroup {
+        let group = StudyGroup {
+            id,
+            public_id: id.to_string(),
+            name: format!("Group {}", id),
+            description: None,
+            creator_id: principal(0),
+            topic_id: None,
+            is_private,
+            max_members: 10,
+            learning_level: "beginner".to_string(),
+            meeting_frequency: None,
+            goals: None,
+            created_at: 0,
+            updated_at: 0,
+            is_archived: false,
+            archived_at: None,
+        };
+        STUDY_GROUPS.with(|groups| groups.borrow_mut().insert(id, group.clone()));
+        group
+    }
+
+    fn membership(id: u64, group_id: u64, user: Principal) -> GroupMembership {
+        GroupMembership {
+            id,
+            user_id: user,
+            group_id,
+            role: "member".to_string(),
+            status: "active".to_string(),
+            joined_at: 0,
+            contributions: 0,
+            last_active_at: None,
+        }
+    }
+
+    // Like `insert_membership`, without refreshing the group's stats.
+    fn insert_membership_row(membership: GroupMembership) {
+        let (id, user, group_id) = (membership.id, membership.user_id, membership.group_id);
+        MEMBERSHIPS_BY_USER.with(|index| index.borrow_mut().insert((user, group_id), id));
+        MEMBERSHIPS_BY_GROUP.with(|index| index.borrow_mut().insert((group_id, user), id));
+        GROUP_MEMBERSHIPS.with(|memberships| memberships.borrow_mut().insert(id, membership));
+    }
+
+    fn add_member(membership_id: u64, group_id: u64, user: Principal) {
+        insert_membership_row(membership(membership_id, group_id, user));
+    }
+
+    fn add_event(id: u64, actor: Principal, event_type: &str, group_id: Option<u64>) {
+        let event = ActivityEvent {
+            id,
+            actor_id: actor,
+            event_type: event_type.to_string(),
+            subject_id: id,
+            group_id,
+            title: event_type.to_string(),
+            created_at: 0,
+        };
+        ACTIVITY_EVENTS.with(|events| events.borrow_mut().insert(id, event));
+        EVENTS_BY_ACTOR.with(|index| index.borrow_mut().insert((actor, id), ()));
+        if let Some(group_id) = group_id {
+            EVENTS_BY_GROUP.with(|index| index.borrow_mut().insert((group_id, id), ()));
+        }
+    }
+
+    fn feed_ids(viewer: Principal) -> Vec<u64> {
+        feed_page(viewer, None, 10).events.iter().map(|event| event.id).collect()
+    }
+
+    #[test]
+    fn discovery_indexes_accept_long_names_and_interests() {
+        let mut user = add_user(principal(1), "connections");
+        user.username = format!("Ada{}", "é".repeat(80));
+        user.settings.interests = vec!["category theory".to_string()];
+        index_user(&user);
+
+        let term = IndexTerm::new(&user.username.to_lowercase());
+        assert!(term.as_str().len() <= state::MAX_INDEX_TERM_BYTES);
+        assert!(user.username.to_lowercase().starts_with(term.as_str()));
+        assert!(USERS_BY_USERNAME.with(|index| index.borrow().contains_key(&(term, user.id))));
+        let interest = (IndexTerm::new("category theory"), user.id);
+        assert!(USERS_BY_INTEREST.with(|index| index.borrow().contains_key(&interest)));
+
+        unindex_user(&user);
+        assert!(USERS_BY_USERNAME.with(|index| index.borrow().is_empty()));
+        assert!(USERS_BY_INTEREST.with(|index| index.borrow().is_empty()));
+    }
+
+    #[test]
+    fn invite_tokens_are_listed_per_group() {
+        let token = "ab".repeat(32);
+        INVITES_BY_GROUP.with(|index| {
+            let mut index = index.borrow_mut();
+            index.insert((7, IndexTerm::new(&token)), ());
+            index.insert((8, IndexTerm::new("cd")), ());
+        });
+        assert_eq!(group_invite_tokens(7), vec![token]);
+        assert!(group_invite_tokens(9).is_empty());
+    }
+
+    #[test]
+    fn retagging_a_resource_moves_its_index_entries() {
+        let mut resource = StudyResource {
+            id: 3,
+            group_id: 7,
+            user_id: principal(1),
+            title: "Notes".to_string(),
+            description: None,
+            resource_type: "note".to_string(),
+            resource_url: None,
+            content: Some("Eigenvalues".to_string()),
+            created_at: 0,
+            updated_at: 0,
+            tags: normalize_tags(vec!["Algebra".to_string(), "matrices".to_string()]).unwrap(),
+            file_name: None,
+            mime_type: None,
+            file_size: None,
+            view_count: 0,
+            download_count: 0,
+        };
+        update_resource_tags(&resource, &[]);
+        let old_tags = std::mem::replace(&mut resource.tags, vec!["matrices".to_string()]);
+        update_resource_tags(&resource, &old_tags);
+
+        let tagged: Vec<(u64, IndexTerm, u64)> =
+            RESOURCE_TAGS.with(|index| index.borrow().iter().map(|(key, _)| key).collect());
+        assert_eq!(tagged, vec![(7, IndexTerm::new("matrices"), 3)]);
+    }
+
+    fn add_task(id: u64, expires_at: Option<u64>) {
+        let task = Task {
+            id,
+            public_id: id.to_string(),
+            title: format!("Task {}", id),
+            description: String::new(),
+            category: "learning".to_string(),
+            difficulty: "easy".to_string(),
+            token_reward: 0,
+            points_reward: 0,
+            requirements: None,
+            is_active: true,
+            is_repeatable: false,
+            max_completions: 1,
+            created_by: principal(9),
+            created_at: 0,
+            expires_at,
+            metadata: None,
+        };
+        if let Some(expires_at) = expires_at {
+            TASKS_BY_EXPIRY.with(|index| index.borrow_mut().insert((expires_at, id), ()));
+        }
+        TASKS.with(|tasks| tasks.borrow_mut().insert(id, task));
+    }
+
+    #[test]
+    fn calendar_lists_open_deadlines_earliest_first_up_to_the_cap() {
+        let user = principal(1);
+        let now = CALENDAR_HISTORY + 1_000;
+        add_task(1, Some(now + 20));
+        add_task(2, Some(now + 10));
+        add_task(3, Some(1));
+        add_task(4, None);
+        add_task(5, Some(now + 30));
+        USER_TASK_COMPLETIONS.with(|completions| {
+            completions.borrow_mut().insert(1, UserTaskCompletion {
+                id: 1,
+                user_id: user,
+                task_id: 5,
+                completed_at: now,
+                tokens_earned: 0,
+                points_earned: 0,
+                completion_count: 1,
+                proof_data: None,
+                metadata: None,
+            })
+        });
+        TASK_COMPLETIONS_BY_USER.with(|index| index.borrow_mut().insert((user, 1), ()));
+
+        let uids: Vec<String> = calendar_events(user, now).into_iter().map(|event| event.uid).collect();
+        assert_eq!(uids, vec!["task-2@cogni", "task-1@cogni"]);
+
+        for id in 10..10 + MAX_CALENDAR_EVENTS as u64 {
+            add_task(id, Some(now + 100 + id));
+        }
+        let events = calendar_events(user, now);
+        assert_eq!(events.len(), MAX_CALENDAR_EVENTS);
+        assert_eq!(events[0].uid, "task-2@cogni");
+    }
+
+    #[test]
+    fn connection_outside_a_private_group_sees_none_of_its_events() {
+        let (viewer, actor) = (principal(1), principal(2));
+        add_user(viewer, "connections");
+        add_user(actor, "connections");
+        connect(1, viewer, actor);
+        add_group(7, true);
+        add_member(1, 7, actor);
+        add_event(1, actor, "group_joined", Some(7));
+        add_event(2, actor, "resource_shared", Some(7));
+        add_event(3, actor, "path_completed", None);
+
+        assert_eq!(feed_ids(viewer), vec![3]);
+
+        // Once the viewer joins, the group's events show up.
+        add_member(2, 7, viewer);
+        assert_eq!(feed_ids(viewer), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn edited_groups_stay_listed() {
+        let admin = principal(1);
+        let group = add_group(7, false);
+        index_group(&group);
+        let membership = GroupMembership { role: "admin".to_string(), ..membership(1, 7, admin) };
+        insert_membership_row(membership);
+        refresh_group_stats(7, Some(5));
+
+        let update = StudyGroupUpdate {
+            name: Some("Linear algebra".to_string()),
+            description: None,
+            is_private: None,
+            max_members: None,
+            learning_level: Some("advanced".to_string()),
+            meeting_frequency: None,
+            goals: None,
+        };
+        edit_study_group(7, admin, update, 10).unwrap();
+
+        for sort_by in ["recent", "members", "newest"] {
+            let filter = StudyGroupFilter {
+                query: None,
+                learning_level: None,
+                topic_id: None,
+                has_capacity: false,
+                public_only: false,
+                sort_by: Some(sort_by.to_string()),
+            };
+            let listed = list_study_groups(filter, 0, 10).unwrap();
+            assert_eq!(listed.len(), 1, "sorted by {}", sort_by);
+            assert_eq!(listed[0].group.name, "Linear algebra");
+            assert_eq!(listed[0].member_count, 1);
+        }
+        let filter = StudyGroupFilter {
+            query: Some("linear".to_string()),
+            learning_level: Some("advanced".to_string()),
+            topic_id: None,
+            has_capacity: true,
+            public_only: false,
+            sort_by: None,
+        };
+        assert_eq!(list_study_groups(filter, 0, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn connection_sees_events_in_public_groups() {
+        let (viewer, actor) = (principal(1), principal(2));
+        add_user(viewer, "connections");
+        add_user(actor, "connections");
+        connect(1, viewer, actor);
+        add_group(7, false);
+        add_member(1, 7, actor);
+        add_event(1, actor, "group_joined", Some(7));
+
+        assert_eq!(feed_ids(viewer), vec![1]);
+    }
+
+    fn participant(user: u8, status: &str, joined_at: u64) -> SessionParticipant {
+        SessionParticipant {
+            id: user as u64,
+            session_id: 1,
+            user_id: Principal::from_slice(&[user]),
+            status: status.to_string(),
+            joined_at,
+        }
+    }
+
+    #[test]
+    fn full_sessions_waitlist_new_confirmations() {
+        assert_eq!(granted_rsvp_status("confirmed", false, 1, 2), "confirmed");
+        assert_eq!(granted_rsvp_status("confirmed", false, 2, 2), "waitlisted");
+        assert_eq!(granted_rsvp_status("confirmed", true, 2, 2), "confirmed");
+        assert_eq!(granted_rsvp_status("declined", false, 2, 2), "declined");
+    }
+
+    #[test]
+    fn confirmed_count_tracks_seat_changes() {
+        assert_eq!(confirmed_count_after(1, false, "confirmed"), 2);
+        assert_eq!(confirmed_count_after(2, true, "declined"), 1);
+        assert_eq!(confirmed_count_after(2, true, "confirmed"), 2);
+        assert_eq!(confirmed_count_after(2, false, "waitlisted"), 2);
+        assert_eq!(confirmed_count_after(0, true, "pending"), 0);
+    }
+
+    #[test]
+    fn waitlist_fills_free_seats_in_joining_order() {
+        let participants = vec![
+            participant(1, "confirmed", 10),
+            participant(2, "waitlisted", 40),
+            participant(3, "declined", 20),
+            participant(4, "waitlisted", 30),
+            participant(5, "waitlisted", 50),
+        ];
+        let promoted: Vec<u64> = waitlist_promotions(participants, 1, 3).iter().map(|p| p.id).collect();
+        assert_eq!(promoted, vec![4, 2]);
+    }
+
+    #[test]
+    fn waitlist_stays_put_without_free_seats() {
+        let participants = vec![participant(1, "waitlisted", 10)];
+        assert!(waitlist_promotions(participants.clone(), 2, 2).is_empty());
+        // The limit can be lowered below the current count; nobody is promoted then either.
+        assert!(waitlist_promotions(participants, 3, 2).is_empty());
+    }
+
+    #[test]
+    fn cancelling_a_seat_promotes_the_next_in_line() {
+        let max_participants = 2;
+        let mut confirmed_count = 0;
+        let mut statuses = vec![];
+        for _ in 0..3 {
+            let status = granted_rsvp_status("confirmed", false, confirmed_count, max_participants);
+            confirmed_count = confirmed_count_after(confirmed_count, false, status);
+            statuses.push(status);
+        }
+        assert_eq!(statuses, vec!["confirmed", "confirmed", "waitlisted"]);
+        assert_eq!(confirmed_count, 2);
+
+        confirmed_count = confirmed_count_after(confirmed_count, true, "declined");
+        let waitlist = vec![participant(3, "waitlisted", 30)];
+        let promoted = waitlist_promotions(waitlist, confirmed_count, max_participants);
+        confirmed_count += promoted.len() as u32;
+        assert_eq!(promoted.len(), 1);
+        assert_eq!(confirmed_count, max_participants);
+    }
+}
@@ -0,0 +1,47 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use ic_stable_structures::storable::{Storable, Bound};
+use std::borrow::Cow;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Certificate {
+    pub id: u64,
+    pub public_id: String,
+    pub user_id: Principal,
+    pub kind: String, // "learning_path", "tutor_course"
+    pub subject_id: u64,
+    pub title: String,
+    pub score: Option<f32>,
+    pub issued_at: u64,
+}
+
+impl Storable for Certificate {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Everything a third party needs to check a certificate: recompute the leaf
+// hash from the Candid encoding of `certificate`, fold it with `proof` up to
+// `root_hash`, and check `root_hash` against the canister's certified data in
+// `data_certificate` (signed by the subnet).
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CertificateProof {
+    pub certificate: Certificate,
+    pub leaf_hash: Vec<u8>,
+    pub proof: Vec<MerkleProofStep>,
+    pub root_hash: Vec<u8>,
+    pub data_certificate: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MerkleProofStep {
+    pub sibling: Vec<u8>,
+    pub sibling_is_left: bool,
+}
@@ -8,7 +8,7 @@ pub struct UserConnection {
     pub id: u64,
     pub user1_id: Principal,
     pub user2_id: Principal,
-    pub status: String, // "active", "blocked"
+    pub status: String, // "active", "ended"
     pub created_at: u64,
     pub updated_at: u64,
 }
@@ -31,7 +31,7 @@ pub struct ConnectionRequest {
     pub id: u64,
     pub sender_id: Principal,
     pub receiver_id: Principal,
-    pub status: String, // "pending", "accepted", "rejected"
+    pub status: String, // "pending", "accepted", "rejected", "cancelled", "expired"
     pub message: Option<String>,
     pub created_at: u64,
     pub updated_at: u64,
@@ -48,4 +48,32 @@ impl Storable for ConnectionRequest {
     }
 
     const BOUND: Bound = Bound::Unbounded;
-} 
\ No newline at end of file
+} 
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ConnectionSuggestion {
+    pub profile: crate::models::user::UserProfile,
+    pub score: f32,
+    pub mutual_connections: u32,
+    pub shared_groups: u32,
+    pub reasons: Vec<String>,
+}
+
+// State of the relationship between two users, keyed by the (min, max) principal pair.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ConnectionPair {
+    pub connection_id: Option<u64>,
+    pub pending_request_id: Option<u64>,
+}
+
+impl Storable for ConnectionPair {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
@@ -0,0 +1,44 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use ic_stable_structures::storable::{Storable, Bound};
+use std::borrow::Cow;
+
+// Something a user did that can show up in other users' feeds.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ActivityEvent {
+    pub id: u64,
+    pub actor_id: Principal,
+    pub event_type: String, // "tutor_published", "path_completed", "achievement_unlocked", etc.
+    pub subject_id: u64,
+    pub group_id: Option<u64>,
+    pub title: String,
+    pub created_at: u64,
+}
+
+impl Storable for ActivityEvent {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// One page of a feed. Pass `next_cursor` back to continue; `None` means the end was reached.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FeedPage {
+    pub events: Vec<ActivityEvent>,
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FollowStats {
+    pub user_id: Principal,
+    pub followers: u64,
+    pub following: u64,
+    pub is_following: bool,
+    pub follows_you: bool,
+}
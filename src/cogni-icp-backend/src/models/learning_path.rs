@@ -15,6 +15,12 @@ pub struct LearningPath {
     pub created_at: u64,
     pub updated_at: u64,
     pub modules: Vec<LearningPathModule>,
+    #[serde(default)]
+    pub enrollment_count: u32,
+    #[serde(default)]
+    pub completion_count: u32,
+    #[serde(default)]
+    pub topic_ids: Vec<u64>,
 }
 
 impl Storable for LearningPath {
@@ -39,4 +45,11 @@ pub struct LearningPathModule {
     pub order: u32,
     pub content: String, // JSON string
     pub resources: Vec<String>,
-} 
\ No newline at end of file
+} 
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PathRecommendation {
+    pub path: LearningPath,
+    pub score: f32,
+    pub reasons: Vec<String>,
+}
@@ -1,5 +1,7 @@
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
+use ic_stable_structures::storable::{Storable, Bound};
+use std::borrow::Cow;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct LearningProgress {
@@ -20,6 +22,20 @@ pub struct LearningPathProgress {
     pub current_module_id: Option<u64>,
     pub progress_percentage: f32,
     pub last_activity: u64,
+    pub enrolled_at: u64,
+    pub completed_at: Option<u64>,
+}
+
+impl Storable for LearningPathProgress {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -29,17 +45,144 @@ pub struct ModuleCompletion {
     pub module_id: u64,
     pub completed: bool,
     pub completion_date: Option<u64>,
+    #[serde(default)]
+    pub learning_path_id: u64,
+}
+
+impl Storable for ModuleCompletion {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct LearningMetrics {
     pub id: u64,
     pub user_id: Principal,
-    pub session_id: u64,
-    pub date: u64,
+    pub session_ids: Vec<u64>,
+    pub date: u64, // Start of the UTC day, in nanoseconds
     pub time_spent_minutes: u32,
     pub messages_sent: u32,
-    pub comprehension_scores: String, // JSON string
-    pub difficulty_adjustments: String, // JSON string
-    pub engagement_metrics: String, // JSON string
-} 
\ No newline at end of file
+    pub modules_completed: u32,
+    pub comprehension_scores: Vec<ComprehensionScore>,
+    pub difficulty_adjustments: Vec<DifficultyAdjustment>,
+    pub engagement_metrics: EngagementMetrics,
+}
+
+impl Storable for LearningMetrics {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ComprehensionScore {
+    pub topic: String,
+    pub score: f32, // 0.0 to 100.0
+    pub session_id: Option<u64>,
+    pub recorded_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DifficultyAdjustment {
+    pub id: u64,
+    pub topic: String,
+    pub from_level: String,
+    pub to_level: String,
+    pub reason: String,
+    pub adjusted_at: u64,
+}
+
+impl Storable for DifficultyAdjustment {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Per-topic effective difficulty for a learner, derived from quiz results and
+// tutor-session comprehension scores.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DifficultyProfile {
+    pub user_id: Principal,
+    pub topics: Vec<TopicDifficulty>,
+    pub updated_at: u64,
+}
+
+impl Storable for DifficultyProfile {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TopicDifficulty {
+    pub topic: String,
+    pub level: String, // "beginner", "intermediate", "advanced"
+    pub recent_scores: Vec<f32>, // Scores since the last adjustment, oldest first
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EngagementMetrics {
+    pub activity_count: u32,
+    pub first_activity_at: u64,
+    pub last_activity_at: u64,
+}
+
+// Input for a single chunk of learning activity reported by the frontend
+// after a tutor session or module interaction.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct LearningActivity {
+    pub session_id: Option<u64>,
+    pub topic: Option<String>,
+    pub minutes: u32,
+    pub messages_sent: u32,
+    pub modules_completed: u32,
+    pub comprehension_score: Option<f32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TimeSpentBucket {
+    pub period_start: u64,
+    pub minutes: u32,
+    pub messages_sent: u32,
+    pub active_days: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LearningStreak {
+    pub current_days: u32,
+    pub longest_days: u32,
+    pub last_active_date: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ComprehensionTrendPoint {
+    pub date: u64,
+    pub average_score: f32,
+    pub samples: u32,
+}
@@ -0,0 +1,61 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use ic_stable_structures::storable::{Storable, Bound};
+use std::borrow::Cow;
+
+// A one-to-one message thread. Participants are stored in (min, max) principal
+// order; each side tracks the last sequence number it has read.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Conversation {
+    pub id: u64,
+    pub user1_id: Principal,
+    pub user2_id: Principal,
+    pub last_seq: u64,
+    pub user1_last_read_seq: u64,
+    pub user2_last_read_seq: u64,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Storable for Conversation {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DirectMessage {
+    pub conversation_id: u64,
+    pub seq: u64,
+    pub sender_id: Principal,
+    pub content: String,
+    pub sent_at: u64,
+    pub is_deleted: bool,
+    pub deleted_at: Option<u64>,
+}
+
+impl Storable for DirectMessage {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ConversationSummary {
+    pub conversation: Conversation,
+    pub other_user_id: Principal,
+    pub last_message: Option<DirectMessage>,
+    pub unread_count: u64,
+}
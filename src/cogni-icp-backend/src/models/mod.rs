@@ -6,4 +6,8 @@ pub mod gamification;
 pub mod notifications;
 pub mod billing;
 pub mod learning_path;
-pub mod learning_progress; 
\ No newline at end of file
+pub mod learning_progress;
+pub mod certificates;
+pub mod messages;
+pub mod feed;
+pub mod http; 
\ No newline at end of file
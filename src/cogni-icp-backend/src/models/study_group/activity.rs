@@ -1,14 +1,53 @@
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
+use ic_stable_structures::storable::{Storable, Bound};
+use std::borrow::Cow;
 
+// An entry in a group's activity log. `user_id` is who acted; `subject_id`
+// points at the message, resource, poll or session involved, and
+// `target_user_id` at the member affected by a membership or role change.
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct GroupActivity {
     pub id: u64,
     pub group_id: u64,
     pub user_id: Principal,
-    pub activity_type: String, // "post", "resource", "question", etc.
+    pub activity_type: String, // "joined", "left", "removed", "banned", "unbanned", "role_changed", "group_updated", "archived", "unarchived", "message", "message_edited", "message_deleted", "resource", "resource_updated", "resource_removed", "poll", "poll_closed", "session", "session_updated", "session_series", "session_series_ended", "session_cancelled", "attended"
     pub content: Option<String>,
     pub created_at: u64,
+    #[serde(default)]
+    pub subject_id: Option<u64>,
+    #[serde(default)]
+    pub target_user_id: Option<Principal>,
+}
+
+impl Storable for GroupActivity {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DailyCount {
+    pub date: u64, // start of the UTC day
+    pub count: u32,
+}
+
+// Members are active on a day if they appear in the group's activity log.
+// `daily_messages` covers the last 30 days, oldest first.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GroupActivityStats {
+    pub group_id: u64,
+    pub active_members_7d: u32,
+    pub active_members_30d: u32,
+    pub messages_per_day_7d: f32,
+    pub messages_per_day_30d: f32,
+    pub daily_messages: Vec<DailyCount>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -22,8 +61,58 @@ pub struct StudyResource {
     pub resource_url: Option<String>,
     pub content: Option<String>,
     pub created_at: u64,
+    pub updated_at: u64,
+    pub tags: Vec<String>,
+    // Set for "file" resources; the bytes are stored separately.
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub file_size: Option<u64>,
+    pub view_count: u64,
+    pub download_count: u64,
+}
+
+impl Storable for StudyResource {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ResourceFile {
+    pub name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
 }
 
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NewStudyResource {
+    pub title: String,
+    pub description: Option<String>,
+    pub resource_type: String,
+    pub resource_url: Option<String>,
+    pub content: Option<String>,
+    pub tags: Vec<String>,
+    pub file: Option<ResourceFile>,
+}
+
+// Fields left as `None` are not changed. Files cannot be replaced; add a new resource instead.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StudyResourceUpdate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub resource_url: Option<String>,
+    pub content: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+// A chat message in a study group. `id` is the message's sequence number within
+// its group, so clients can poll for anything newer than the last id they saw.
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct GroupMessage {
     pub id: u64,
@@ -32,4 +121,21 @@ pub struct GroupMessage {
     pub content: String,
     pub timestamp: u64,
     pub attachments: Option<Vec<String>>,
+    pub reply_to: Option<u64>, // id of the thread's root message
+    pub reply_count: u32,
+    pub edited_at: Option<u64>,
+    pub is_deleted: bool,
+    pub deleted_by: Option<Principal>,
+}
+
+impl Storable for GroupMessage {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
 } 
\ No newline at end of file
@@ -6,6 +6,7 @@ use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 use ic_stable_structures::storable::{Storable, Bound};
 use std::borrow::Cow;
+use crate::models::{learning_path::LearningPath, tutor::Tutor};
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct StudyGroup {
@@ -22,6 +23,24 @@ pub struct StudyGroup {
     pub goals: Option<String>,
     pub created_at: u64,
     pub updated_at: u64,
+    // Archived groups are read-only until unarchived.
+    #[serde(default)]
+    pub is_archived: bool,
+    #[serde(default)]
+    pub archived_at: Option<u64>,
+}
+
+// Fields left as `None` are not changed. Optional text fields are cleared by
+// passing an empty string.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StudyGroupUpdate {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub is_private: Option<bool>,
+    pub max_members: Option<u32>,
+    pub learning_level: Option<String>,
+    pub meeting_frequency: Option<String>,
+    pub goals: Option<String>,
 }
 
 impl Storable for StudyGroup {
@@ -60,6 +79,94 @@ impl Storable for GroupMembership {
     const BOUND: Bound = Bound::Unbounded;
 }
 
+// Denormalized counters kept per group so listings can be sorted from an index.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StudyGroupStats {
+    pub member_count: u32,
+    pub last_activity_at: u64,
+}
+
+impl Storable for StudyGroupStats {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StudyGroupSummary {
+    pub group: StudyGroup,
+    pub member_count: u32,
+    pub last_activity_at: u64,
+}
+
+// Filters for `list_study_groups`. Archived groups are never listed.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StudyGroupFilter {
+    pub query: Option<String>, // matched against words in the name and description
+    pub learning_level: Option<String>,
+    pub topic_id: Option<u64>,
+    pub has_capacity: bool,
+    pub public_only: bool,
+    pub sort_by: Option<String>, // "recent" (default), "members", "newest"
+}
+
+// A request to join a private group, decided by one of its admins.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GroupJoinRequest {
+    pub id: u64,
+    pub group_id: u64,
+    pub user_id: Principal,
+    pub status: String, // "pending", "approved", "rejected", "cancelled"
+    pub message: Option<String>,
+    pub created_at: u64,
+    pub responded_at: Option<u64>,
+    pub responded_by: Option<Principal>,
+}
+
+impl Storable for GroupJoinRequest {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// An invite link token. Anyone holding an unexpired, unrevoked token can join
+// the group without approval until `max_uses` is reached.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GroupInvite {
+    pub token: String,
+    pub group_id: u64,
+    pub created_by: Principal,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+    pub revoked: bool,
+}
+
+impl Storable for GroupInvite {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Topic {
     pub id: u64,
@@ -69,4 +176,25 @@ pub struct Topic {
     pub difficulty_level: Option<String>,
     pub keywords: Option<String>,
     pub created_at: u64,
+}
+
+impl Storable for Topic {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Everything tagged with a topic (and optionally its subtopics), for browsing by subject.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TopicContents {
+    pub topics: Vec<Topic>,
+    pub study_groups: Vec<StudyGroup>,
+    pub learning_paths: Vec<LearningPath>,
+    pub tutors: Vec<Tutor>,
 } 
\ No newline at end of file
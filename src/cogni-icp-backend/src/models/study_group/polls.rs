@@ -1,5 +1,7 @@
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
+use ic_stable_structures::storable::{Storable, Bound};
+use std::borrow::Cow;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct GroupPoll {
@@ -10,8 +12,26 @@ pub struct GroupPoll {
     pub created_at: u64,
     pub expires_at: Option<u64>,
     pub is_active: bool,
+    pub allow_multiple: bool,
+    // Anonymous polls never reveal who voted for what.
+    pub is_anonymous: bool,
+    pub hide_results_until_closed: bool,
+    pub closed_at: Option<u64>,
 }
 
+impl Storable for GroupPoll {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Option ids are numbered from 1 within their poll.
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct PollOption {
     pub id: u64,
@@ -19,6 +39,18 @@ pub struct PollOption {
     pub text: String,
 }
 
+impl Storable for PollOption {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct PollVote {
     pub id: u64,
@@ -26,4 +58,43 @@ pub struct PollVote {
     pub option_id: u64,
     pub user_id: Principal,
     pub timestamp: u64,
-} 
\ No newline at end of file
+}
+
+impl Storable for PollVote {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NewPoll {
+    pub question: String,
+    pub options: Vec<String>,
+    pub allow_multiple: bool,
+    pub is_anonymous: bool,
+    pub hide_results_until_closed: bool,
+    pub expires_at: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PollOptionResult {
+    pub option: PollOption,
+    pub votes: u64,
+    pub voters: Option<Vec<Principal>>, // None for anonymous polls
+}
+
+// Tallies are empty while `results_hidden` is set.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PollResults {
+    pub poll: GroupPoll,
+    pub options: Vec<PollOptionResult>,
+    pub total_voters: u64,
+    pub my_votes: Vec<u64>,
+    pub results_hidden: bool,
+}
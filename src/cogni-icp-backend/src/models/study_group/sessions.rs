@@ -1,6 +1,11 @@
 use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
+use ic_stable_structures::storable::{Storable, Bound};
+use std::borrow::Cow;
 
+// A scheduled study session. `starts_at` is a UTC timestamp in nanoseconds;
+// `timezone` and `utc_offset_minutes` record the organizer's local time so
+// clients can show the session as it was scheduled.
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct StudySession {
     pub id: u64,
@@ -8,12 +13,34 @@ pub struct StudySession {
     pub creator_id: Principal,
     pub title: String,
     pub description: Option<String>,
-    pub date: String, // Using String for date "YYYY-MM-DD"
-    pub time: String, // Using String for time "HH:MM"
+    pub starts_at: u64,
+    pub timezone: String, // IANA name, e.g. "Europe/Berlin"
+    pub utc_offset_minutes: i32,
     pub duration_minutes: u32,
     pub max_participants: u32,
     pub topics: Vec<String>,
+    pub status: String, // "scheduled", "cancelled"
+    pub confirmed_count: u32,
     pub created_at: u64,
+    pub updated_at: u64,
+    // Set for occurrences of a recurring series. `occurrence_start` is the slot
+    // the rule produced, which stays fixed if this occurrence is moved.
+    #[serde(default)]
+    pub series_id: Option<u64>,
+    #[serde(default)]
+    pub occurrence_start: Option<u64>,
+}
+
+impl Storable for StudySession {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -21,6 +48,140 @@ pub struct SessionParticipant {
     pub id: u64,
     pub session_id: u64,
     pub user_id: Principal,
-    pub status: String, // "confirmed", "pending", "declined"
+    pub status: String, // "confirmed", "waitlisted", "pending", "declined"
     pub joined_at: u64,
-} 
\ No newline at end of file
+}
+
+impl Storable for SessionParticipant {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NewStudySession {
+    pub title: String,
+    pub description: Option<String>,
+    pub starts_at: u64,
+    pub timezone: String,
+    pub utc_offset_minutes: i32,
+    pub duration_minutes: u32,
+    pub max_participants: u32,
+    pub topics: Vec<String>,
+}
+
+// Fields left as `None` are not changed.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StudySessionUpdate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub starts_at: Option<u64>,
+    pub duration_minutes: Option<u32>,
+    pub max_participants: Option<u32>,
+    pub topics: Option<Vec<String>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RecurrenceRule {
+    pub frequency: String, // "weekly", "monthly"
+    pub interval: u32, // repeat every N weeks or months
+    pub weekdays: Vec<u8>, // weekly only; 0 = Monday .. 6 = Sunday
+}
+
+// A recurring session. Concrete `StudySession`s are generated from it a few
+// weeks ahead; skipping or moving one occurrence only changes that session.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SessionSeries {
+    pub id: u64,
+    pub group_id: u64,
+    pub creator_id: Principal,
+    pub title: String,
+    pub description: Option<String>,
+    pub first_start: u64,
+    pub until: Option<u64>,
+    pub timezone: String,
+    pub utc_offset_minutes: i32,
+    pub duration_minutes: u32,
+    pub max_participants: u32,
+    pub topics: Vec<String>,
+    pub rule: RecurrenceRule,
+    pub generated_until: u64,
+    pub status: String, // "active", "ended"
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl Storable for SessionSeries {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UserSession {
+    pub session: StudySession,
+    pub rsvp_status: String,
+}
+
+// A confirmed check-in. `rsvp_status` is the RSVP the user had at the time,
+// or "none" for walk-ins, so attendance can be compared against RSVPs.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SessionAttendance {
+    pub session_id: u64,
+    pub user_id: Principal,
+    pub rsvp_status: String,
+    pub checked_in_at: u64,
+    pub recorded_by: Principal,
+    pub points_earned: u32,
+}
+
+impl Storable for SessionAttendance {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Attendance over sessions that have already ended. `sessions` counts the
+// sessions held for a group, and the sessions confirmed or attended for a
+// member. `attendance_rate` is attended / confirmed, or 0 when nobody confirmed.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AttendanceStats {
+    pub sessions: u32,
+    pub confirmed: u32,
+    pub attended: u32,
+    pub walk_ins: u32,
+    pub no_shows: u32,
+    pub attendance_rate: f32,
+    pub points_earned: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MemberAttendance {
+    pub user_id: Principal,
+    pub stats: AttendanceStats,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GroupAttendanceReport {
+    pub group_id: u64,
+    pub totals: AttendanceStats,
+    pub members: Vec<MemberAttendance>,
+}
@@ -21,6 +21,16 @@ pub struct Tutor {
     pub voice_settings: HashMap<String, String>,
     pub created_at: u64,
     pub updated_at: u64,
+    #[serde(default)]
+    pub topic_ids: Vec<u64>,
+    // Tutors stored before visibility existed were visible to everyone, so
+    // they load as public.
+    #[serde(default = "default_is_public")]
+    pub is_public: bool,
+}
+
+fn default_is_public() -> bool {
+    true
 }
 
 impl Storable for Tutor {
@@ -80,6 +90,37 @@ pub struct TutorCourse {
     pub estimated_duration: String,
     pub created_at: u64,
     pub modules: Vec<CourseModule>,
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+}
+
+impl Storable for TutorCourse {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(serde_cbor::to_vec(&self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_cbor::from_slice(bytes.as_ref()).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// A course generated in a tutor session. Module ids are assigned in order.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NewTutorCourse {
+    pub topic: String,
+    pub outline: String,
+    pub difficulty_level: String,
+    pub estimated_duration: String,
+    pub modules: Vec<NewCourseModule>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NewCourseModule {
+    pub title: String,
+    pub description: String,
+    pub content: Option<String>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -50,6 +50,9 @@ pub struct UserSettings {
     // Privacy Settings
     pub profile_visibility: String,
     pub activity_sharing: String,
+    // Interests
+    #[serde(default)]
+    pub interests: Vec<String>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -71,6 +74,38 @@ pub struct LoginSession {
     pub is_active: bool,
 }
 
+// The subset of a user's profile that other users are allowed to see.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UserProfile {
+    pub id: Principal,
+    pub public_id: String,
+    pub username: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub location: Option<String>,
+    pub role: String,
+    pub created_at: u64,
+}
+
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        UserProfile {
+            id: user.id,
+            public_id: user.public_id,
+            username: user.username,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            avatar_url: user.avatar_url,
+            bio: user.bio,
+            location: user.location,
+            role: user.role,
+            created_at: user.created_at,
+        }
+    }
+}
+
 impl Storable for User {
     fn to_bytes(&self) -> Cow<[u8]> {
         Cow::Owned(serde_cbor::to_vec(&self).unwrap())
@@ -1,16 +1,20 @@
 use crate::models::{
     user::User,
-    tutor::{Tutor, TutorSession},
+    tutor::{Tutor, TutorSession, TutorCourse},
     learning_path::LearningPath,
-    connections::{UserConnection, ConnectionRequest},
+    learning_progress::{LearningMetrics, DifficultyProfile, DifficultyAdjustment, LearningPathProgress, ModuleCompletion},
+    connections::{UserConnection, ConnectionRequest, ConnectionPair},
     study_group::{
-        StudyGroup, GroupMembership,
+        StudyGroup, StudyGroupStats, GroupMembership, GroupJoinRequest, GroupInvite, Topic,
         activity::{GroupActivity, StudyResource, GroupMessage},
-        polls::{GroupPoll, PollVote},
-        sessions::{StudySession, SessionParticipant},
+        polls::{GroupPoll, PollOption, PollVote},
+        sessions::{StudySession, SessionParticipant, SessionSeries, SessionAttendance},
     },
     billing::{SubscriptionPlan, UserSubscription, PaymentTransaction},
     gamification::{Achievement, UserAchievement, Task, UserTaskCompletion},
+    certificates::Certificate,
+    messages::{Conversation, DirectMessage},
+    feed::ActivityEvent,
 };
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
@@ -19,7 +23,7 @@ use std::borrow::Cow;
 use std::cell::RefCell;
 use candid::Principal;
 
-type Memory = VirtualMemory<DefaultMemoryImpl>;
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 
 const USER_MEMORY_ID: MemoryId = MemoryId::new(0);
 const TUTOR_MEMORY_ID: MemoryId = MemoryId::new(1);
@@ -36,12 +40,88 @@ const ACHIEVEMENT_MEMORY_ID: MemoryId = MemoryId::new(11);
 const USER_ACHIEVEMENT_MEMORY_ID: MemoryId = MemoryId::new(12);
 const TASK_MEMORY_ID: MemoryId = MemoryId::new(13);
 const USER_TASK_COMPLETION_MEMORY_ID: MemoryId = MemoryId::new(14);
+const LEARNING_METRICS_MEMORY_ID: MemoryId = MemoryId::new(15);
+const DIFFICULTY_PROFILE_MEMORY_ID: MemoryId = MemoryId::new(16);
+const DIFFICULTY_ADJUSTMENT_MEMORY_ID: MemoryId = MemoryId::new(17);
+const LEARNING_PATH_PROGRESS_MEMORY_ID: MemoryId = MemoryId::new(18);
+const TOPIC_MEMORY_ID: MemoryId = MemoryId::new(19);
 
 
 const ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(20);
 
+const TOPIC_GROUP_INDEX_MEMORY_ID: MemoryId = MemoryId::new(21);
+const TOPIC_PATH_INDEX_MEMORY_ID: MemoryId = MemoryId::new(22);
+const TOPIC_TUTOR_INDEX_MEMORY_ID: MemoryId = MemoryId::new(23);
+const CERTIFICATE_MEMORY_ID: MemoryId = MemoryId::new(24);
+const CERTIFICATE_LEAF_MEMORY_ID: MemoryId = MemoryId::new(25);
+const CERTIFICATES_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(26);
+const CONNECTION_PAIR_MEMORY_ID: MemoryId = MemoryId::new(27);
+const CONNECTIONS_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(28);
+const TUTORS_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(29);
+const MEMBERSHIPS_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(30);
+const MEMBERSHIPS_BY_GROUP_MEMORY_ID: MemoryId = MemoryId::new(31);
+const TASK_COMPLETIONS_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(32);
+const TUTOR_SESSIONS_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(33);
+const BLOCK_MEMORY_ID: MemoryId = MemoryId::new(34);
+const CONVERSATION_MEMORY_ID: MemoryId = MemoryId::new(35);
+const DIRECT_MESSAGE_MEMORY_ID: MemoryId = MemoryId::new(36);
+const CONVERSATIONS_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(37);
+const CONVERSATION_PAIR_MEMORY_ID: MemoryId = MemoryId::new(38);
+const USERNAME_INDEX_MEMORY_ID: MemoryId = MemoryId::new(39);
+const INTEREST_INDEX_MEMORY_ID: MemoryId = MemoryId::new(40);
+const FOLLOW_MEMORY_ID: MemoryId = MemoryId::new(41);
+const FOLLOWER_MEMORY_ID: MemoryId = MemoryId::new(42);
+const ACTIVITY_EVENT_MEMORY_ID: MemoryId = MemoryId::new(43);
+const EVENTS_BY_ACTOR_MEMORY_ID: MemoryId = MemoryId::new(44);
+const EVENTS_BY_GROUP_MEMORY_ID: MemoryId = MemoryId::new(45);
+const GROUP_JOIN_REQUEST_MEMORY_ID: MemoryId = MemoryId::new(46);
+const PENDING_JOIN_REQUEST_MEMORY_ID: MemoryId = MemoryId::new(47);
+const GROUP_INVITE_MEMORY_ID: MemoryId = MemoryId::new(48);
+const GROUP_STATS_MEMORY_ID: MemoryId = MemoryId::new(49);
+const GROUPS_BY_MEMBERS_MEMORY_ID: MemoryId = MemoryId::new(50);
+const GROUPS_BY_ACTIVITY_MEMORY_ID: MemoryId = MemoryId::new(51);
+const GROUPS_BY_LEVEL_MEMORY_ID: MemoryId = MemoryId::new(52);
+const GROUP_KEYWORD_MEMORY_ID: MemoryId = MemoryId::new(53);
+const GROUP_MESSAGE_MEMORY_ID: MemoryId = MemoryId::new(54);
+const GROUP_MESSAGE_REPLY_MEMORY_ID: MemoryId = MemoryId::new(55);
+const STUDY_RESOURCE_MEMORY_ID: MemoryId = MemoryId::new(56);
+const RESOURCES_BY_GROUP_MEMORY_ID: MemoryId = MemoryId::new(57);
+const RESOURCE_FILE_MEMORY_ID: MemoryId = MemoryId::new(58);
+const RESOURCE_TAG_MEMORY_ID: MemoryId = MemoryId::new(59);
+const GROUP_POLL_MEMORY_ID: MemoryId = MemoryId::new(60);
+const POLLS_BY_GROUP_MEMORY_ID: MemoryId = MemoryId::new(61);
+const POLL_OPTION_MEMORY_ID: MemoryId = MemoryId::new(62);
+const POLL_VOTE_MEMORY_ID: MemoryId = MemoryId::new(63);
+const STUDY_SESSION_MEMORY_ID: MemoryId = MemoryId::new(64);
+const SESSIONS_BY_GROUP_MEMORY_ID: MemoryId = MemoryId::new(65);
+const SESSION_PARTICIPANT_MEMORY_ID: MemoryId = MemoryId::new(66);
+const SESSIONS_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(67);
+const SESSION_SERIES_MEMORY_ID: MemoryId = MemoryId::new(68);
+const SERIES_OCCURRENCE_MEMORY_ID: MemoryId = MemoryId::new(69);
+const CALENDAR_TOKEN_MEMORY_ID: MemoryId = MemoryId::new(70);
+const USER_CALENDAR_TOKEN_MEMORY_ID: MemoryId = MemoryId::new(71);
+const SESSION_ATTENDANCE_MEMORY_ID: MemoryId = MemoryId::new(72);
+const GROUP_ACTIVITY_MEMORY_ID: MemoryId = MemoryId::new(73);
+const GROUP_ACTIVE_MEMBERS_MEMORY_ID: MemoryId = MemoryId::new(74);
+const GROUP_DAILY_MESSAGES_MEMORY_ID: MemoryId = MemoryId::new(75);
+const PATH_MODULE_COMPLETION_MEMORY_ID: MemoryId = MemoryId::new(76);
+const CERTIFICATE_POSITION_MEMORY_ID: MemoryId = MemoryId::new(77);
+const CERTIFICATE_TREE_MEMORY_ID: MemoryId = MemoryId::new(78);
+const USER_ACHIEVEMENTS_BY_USER_MEMORY_ID: MemoryId = MemoryId::new(79);
+const GROUP_FILE_BYTES_MEMORY_ID: MemoryId = MemoryId::new(80);
+const TUTOR_COURSE_MEMORY_ID: MemoryId = MemoryId::new(81);
+const COURSES_BY_SESSION_MEMORY_ID: MemoryId = MemoryId::new(82);
+const REQUESTS_BY_SENDER_MEMORY_ID: MemoryId = MemoryId::new(83);
+const REQUESTS_BY_RECEIVER_MEMORY_ID: MemoryId = MemoryId::new(84);
+const PENDING_REQUESTS_BY_AGE_MEMORY_ID: MemoryId = MemoryId::new(85);
+const INVITES_BY_GROUP_MEMORY_ID: MemoryId = MemoryId::new(86);
+const SERIES_BY_GROUP_MEMORY_ID: MemoryId = MemoryId::new(87);
+const TASKS_BY_EXPIRY_MEMORY_ID: MemoryId = MemoryId::new(88);
 
+
+// Fields missing from older serialized counters default to zero.
 #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+#[serde(default)]
 struct IdCounters {
     tutor: u64,
     tutor_session: u64,
@@ -57,6 +137,23 @@ struct IdCounters {
     user_achievement: u64,
     task: u64,
     user_task_completion: u64,
+    learning_metrics: u64,
+    difficulty_adjustment: u64,
+    learning_path_progress: u64,
+    topic: u64,
+    certificate: u64,
+    conversation: u64,
+    activity_event: u64,
+    group_join_request: u64,
+    study_resource: u64,
+    poll: u64,
+    poll_vote: u64,
+    study_session: u64,
+    session_participant: u64,
+    session_series: u64,
+    group_activity: u64,
+    module_completion: u64,
+    tutor_course: u64,
 }
 
 impl Storable for IdCounters {
@@ -71,6 +168,39 @@ impl Storable for IdCounters {
     const BOUND: Bound = Bound::Unbounded;
 }
 
+// String part of a composite index key. Tuple keys only serialize when every
+// element is bounded, so terms are cut to MAX_INDEX_TERM_BYTES on creation.
+pub const MAX_INDEX_TERM_BYTES: usize = 100;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IndexTerm(String);
+
+impl IndexTerm {
+    pub fn new(term: &str) -> Self {
+        let mut end = term.len().min(MAX_INDEX_TERM_BYTES);
+        while !term.is_char_boundary(end) {
+            end -= 1;
+        }
+        IndexTerm(term[..end].to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Storable for IndexTerm {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        IndexTerm(String::from_utf8(bytes.into_owned()).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Bounded { max_size: MAX_INDEX_TERM_BYTES as u32, is_fixed_size: false };
+}
+
 thread_local! {
     // The memory manager is used for managing memory allocation for stable structures.
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
@@ -83,6 +213,20 @@ thread_local! {
         )
     );
 
+    // Lowercased username -> user, for prefix search
+    pub static USERS_BY_USERNAME: RefCell<StableBTreeMap<(IndexTerm, Principal), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(USERNAME_INDEX_MEMORY_ID)),
+        )
+    );
+
+    // Interest tag -> users who listed it
+    pub static USERS_BY_INTEREST: RefCell<StableBTreeMap<(IndexTerm, Principal), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(INTEREST_INDEX_MEMORY_ID)),
+        )
+    );
+
     // Stable storage for Tutors
     pub static TUTORS: RefCell<StableBTreeMap<u64, Tutor, Memory>> = RefCell::new(
         StableBTreeMap::init(
@@ -97,6 +241,19 @@ thread_local! {
         )
     );
 
+    pub static TUTOR_COURSES: RefCell<StableBTreeMap<u64, TutorCourse, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TUTOR_COURSE_MEMORY_ID)),
+        )
+    );
+
+    // (tutor session id, course id)
+    pub static COURSES_BY_SESSION: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(COURSES_BY_SESSION_MEMORY_ID)),
+        )
+    );
+
     // Stable storage for Learning Paths
     pub static LEARNING_PATHS: RefCell<StableBTreeMap<u64, LearningPath, Memory>> = RefCell::new(
         StableBTreeMap::init(
@@ -118,6 +275,102 @@ thread_local! {
         )
     );
 
+    // (sender, request id) for every connection request
+    pub static REQUESTS_BY_SENDER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REQUESTS_BY_SENDER_MEMORY_ID)),
+        )
+    );
+
+    // (receiver, request id) for every connection request
+    pub static REQUESTS_BY_RECEIVER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(REQUESTS_BY_RECEIVER_MEMORY_ID)),
+        )
+    );
+
+    // (created at, request id) for pending requests, oldest first, for expiry
+    pub static PENDING_REQUESTS_BY_AGE: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PENDING_REQUESTS_BY_AGE_MEMORY_ID)),
+        )
+    );
+
+    // Connection state per user pair, keyed by (min principal, max principal)
+    pub static CONNECTION_PAIRS: RefCell<StableBTreeMap<(Principal, Principal), ConnectionPair, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONNECTION_PAIR_MEMORY_ID)),
+        )
+    );
+
+    // Block list, keyed by (blocker, blocked user) with the time the block was created
+    pub static BLOCKS: RefCell<StableBTreeMap<(Principal, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(BLOCK_MEMORY_ID)),
+        )
+    );
+
+    // Direct messaging
+    pub static CONVERSATIONS: RefCell<StableBTreeMap<u64, Conversation, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONVERSATION_MEMORY_ID)),
+        )
+    );
+
+    // Messages keyed by (conversation id, sequence number within the conversation)
+    pub static DIRECT_MESSAGES: RefCell<StableBTreeMap<(u64, u64), DirectMessage, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DIRECT_MESSAGE_MEMORY_ID)),
+        )
+    );
+
+    pub static CONVERSATIONS_BY_USER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONVERSATIONS_BY_USER_MEMORY_ID)),
+        )
+    );
+
+    // (min principal, max principal) -> conversation id
+    pub static CONVERSATION_PAIRS: RefCell<StableBTreeMap<(Principal, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONVERSATION_PAIR_MEMORY_ID)),
+        )
+    );
+
+    // (follower, followee) -> followed at
+    pub static FOLLOWS: RefCell<StableBTreeMap<(Principal, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(FOLLOW_MEMORY_ID)),
+        )
+    );
+
+    // (followee, follower) -> followed at
+    pub static FOLLOWERS: RefCell<StableBTreeMap<(Principal, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(FOLLOWER_MEMORY_ID)),
+        )
+    );
+
+    // Append-only log of feed events
+    pub static ACTIVITY_EVENTS: RefCell<StableBTreeMap<u64, ActivityEvent, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ACTIVITY_EVENT_MEMORY_ID)),
+        )
+    );
+
+    pub static EVENTS_BY_ACTOR: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(EVENTS_BY_ACTOR_MEMORY_ID)),
+        )
+    );
+
+    // (group id, event id) for events that happened inside a study group
+    pub static EVENTS_BY_GROUP: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(EVENTS_BY_GROUP_MEMORY_ID)),
+        )
+    );
+
     // Stable storage for Study Groups
     pub static STUDY_GROUPS: RefCell<StableBTreeMap<u64, StudyGroup, Memory>> = RefCell::new(
         StableBTreeMap::init(
@@ -125,6 +378,230 @@ thread_local! {
         )
     );
 
+    pub static GROUP_STATS: RefCell<StableBTreeMap<u64, StudyGroupStats, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_STATS_MEMORY_ID)),
+        )
+    );
+
+    // Group discovery indexes, keyed by (sort or filter value, group id)
+    pub static GROUPS_BY_MEMBERS: RefCell<StableBTreeMap<(u32, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUPS_BY_MEMBERS_MEMORY_ID)),
+        )
+    );
+
+    pub static GROUPS_BY_ACTIVITY: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUPS_BY_ACTIVITY_MEMORY_ID)),
+        )
+    );
+
+    pub static GROUPS_BY_LEVEL: RefCell<StableBTreeMap<(IndexTerm, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUPS_BY_LEVEL_MEMORY_ID)),
+        )
+    );
+
+    // Lowercased words from group names and descriptions
+    pub static GROUP_KEYWORDS: RefCell<StableBTreeMap<(IndexTerm, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_KEYWORD_MEMORY_ID)),
+        )
+    );
+
+    // Group chat keyed by (group id, message sequence number)
+    pub static GROUP_MESSAGES: RefCell<StableBTreeMap<(u64, u64), GroupMessage, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_MESSAGE_MEMORY_ID)),
+        )
+    );
+
+    // (group id, thread root id, reply id)
+    pub static GROUP_MESSAGE_REPLIES: RefCell<StableBTreeMap<(u64, u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_MESSAGE_REPLY_MEMORY_ID)),
+        )
+    );
+
+    // Shared study resources
+    pub static STUDY_RESOURCES: RefCell<StableBTreeMap<u64, StudyResource, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(STUDY_RESOURCE_MEMORY_ID)),
+        )
+    );
+
+    pub static RESOURCES_BY_GROUP: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(RESOURCES_BY_GROUP_MEMORY_ID)),
+        )
+    );
+
+    // Uploaded file contents, keyed by resource id
+    pub static RESOURCE_FILES: RefCell<StableBTreeMap<u64, Vec<u8>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(RESOURCE_FILE_MEMORY_ID)),
+        )
+    );
+
+    // Running total of uploaded file bytes, keyed by group id
+    pub static GROUP_FILE_BYTES: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_FILE_BYTES_MEMORY_ID)),
+        )
+    );
+
+    // (group id, tag, resource id)
+    pub static RESOURCE_TAGS: RefCell<StableBTreeMap<(u64, IndexTerm, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(RESOURCE_TAG_MEMORY_ID)),
+        )
+    );
+
+    // Group polls
+    pub static GROUP_POLLS: RefCell<StableBTreeMap<u64, GroupPoll, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_POLL_MEMORY_ID)),
+        )
+    );
+
+    pub static POLLS_BY_GROUP: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(POLLS_BY_GROUP_MEMORY_ID)),
+        )
+    );
+
+    // (poll id, option id) -> option
+    pub static POLL_OPTIONS: RefCell<StableBTreeMap<(u64, u64), PollOption, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(POLL_OPTION_MEMORY_ID)),
+        )
+    );
+
+    // (poll id, voter, option id) -> vote
+    pub static POLL_VOTES: RefCell<StableBTreeMap<(u64, Principal, u64), PollVote, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(POLL_VOTE_MEMORY_ID)),
+        )
+    );
+
+    // Scheduled study sessions
+    pub static STUDY_SESSIONS: RefCell<StableBTreeMap<u64, StudySession, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(STUDY_SESSION_MEMORY_ID)),
+        )
+    );
+
+    // (group id, starts at, session id)
+    pub static SESSIONS_BY_GROUP: RefCell<StableBTreeMap<(u64, u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SESSIONS_BY_GROUP_MEMORY_ID)),
+        )
+    );
+
+    // (session id, user) -> RSVP
+    pub static SESSION_PARTICIPANTS: RefCell<StableBTreeMap<(u64, Principal), SessionParticipant, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SESSION_PARTICIPANT_MEMORY_ID)),
+        )
+    );
+
+    // (user, starts at, session id) for every session the user has responded to or checked in to
+    pub static SESSIONS_BY_USER: RefCell<StableBTreeMap<(Principal, u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SESSIONS_BY_USER_MEMORY_ID)),
+        )
+    );
+
+    pub static SESSION_SERIES: RefCell<StableBTreeMap<u64, SessionSeries, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SESSION_SERIES_MEMORY_ID)),
+        )
+    );
+
+    // (group id, series id) for every recurring session series
+    pub static SERIES_BY_GROUP: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SERIES_BY_GROUP_MEMORY_ID)),
+        )
+    );
+
+    // (series id, occurrence start) -> session id for every generated occurrence
+    pub static SERIES_OCCURRENCES: RefCell<StableBTreeMap<(u64, u64), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SERIES_OCCURRENCE_MEMORY_ID)),
+        )
+    );
+
+    // (group id, activity id) -> log entry
+    pub static GROUP_ACTIVITIES: RefCell<StableBTreeMap<(u64, u64), GroupActivity, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_ACTIVITY_MEMORY_ID)),
+        )
+    );
+
+    // (group id, day, user) -> actions that day; days older than the stats window are pruned
+    pub static GROUP_ACTIVE_MEMBERS: RefCell<StableBTreeMap<(u64, u64, Principal), u32, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_ACTIVE_MEMBERS_MEMORY_ID)),
+        )
+    );
+
+    // (group id, day) -> messages posted that day
+    pub static GROUP_DAILY_MESSAGES: RefCell<StableBTreeMap<(u64, u64), u32, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_DAILY_MESSAGES_MEMORY_ID)),
+        )
+    );
+
+    // (session id, user) -> check-in
+    pub static SESSION_ATTENDANCE: RefCell<StableBTreeMap<(u64, Principal), SessionAttendance, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SESSION_ATTENDANCE_MEMORY_ID)),
+        )
+    );
+
+    // Calendar feed secret -> owner, and the reverse for rotation
+    pub static CALENDAR_TOKENS: RefCell<StableBTreeMap<String, Principal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CALENDAR_TOKEN_MEMORY_ID)),
+        )
+    );
+
+    pub static USER_CALENDAR_TOKENS: RefCell<StableBTreeMap<Principal, String, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(USER_CALENDAR_TOKEN_MEMORY_ID)),
+        )
+    );
+
+    // Join requests for private groups
+    pub static GROUP_JOIN_REQUESTS: RefCell<StableBTreeMap<u64, GroupJoinRequest, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_JOIN_REQUEST_MEMORY_ID)),
+        )
+    );
+
+    // (group id, user) -> pending join request id
+    pub static PENDING_JOIN_REQUESTS: RefCell<StableBTreeMap<(u64, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PENDING_JOIN_REQUEST_MEMORY_ID)),
+        )
+    );
+
+    // Invite token -> invite
+    pub static GROUP_INVITES: RefCell<StableBTreeMap<String, GroupInvite, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(GROUP_INVITE_MEMORY_ID)),
+        )
+    );
+
+    // (group id, invite token)
+    pub static INVITES_BY_GROUP: RefCell<StableBTreeMap<(u64, IndexTerm), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(INVITES_BY_GROUP_MEMORY_ID)),
+        )
+    );
+
     // Stable storage for Group Memberships
     pub static GROUP_MEMBERSHIPS: RefCell<StableBTreeMap<u64, GroupMembership, Memory>> = RefCell::new(
         StableBTreeMap::init(
@@ -164,18 +641,167 @@ thread_local! {
         )
     );
 
+    // (user, achievement id) -> user achievement id
+    pub static USER_ACHIEVEMENTS_BY_USER: RefCell<StableBTreeMap<(Principal, u64), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(USER_ACHIEVEMENTS_BY_USER_MEMORY_ID)),
+        )
+    );
+
     pub static TASKS: RefCell<StableBTreeMap<u64, Task, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(TASK_MEMORY_ID)),
         )
     );
 
+    // (expires at, task id) for tasks with a deadline
+    pub static TASKS_BY_EXPIRY: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TASKS_BY_EXPIRY_MEMORY_ID)),
+        )
+    );
+
     pub static USER_TASK_COMPLETIONS: RefCell<StableBTreeMap<u64, UserTaskCompletion, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(USER_TASK_COMPLETION_MEMORY_ID)),
         )
     );
 
+    // Daily learning metrics, keyed by (user, day index since the epoch)
+    pub static LEARNING_METRICS: RefCell<StableBTreeMap<(Principal, u64), LearningMetrics, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LEARNING_METRICS_MEMORY_ID)),
+        )
+    );
+
+    // Adaptive difficulty state per learner
+    pub static DIFFICULTY_PROFILES: RefCell<StableBTreeMap<Principal, DifficultyProfile, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DIFFICULTY_PROFILE_MEMORY_ID)),
+        )
+    );
+
+    // History of difficulty changes, keyed by (user, adjustment id)
+    pub static DIFFICULTY_ADJUSTMENTS: RefCell<StableBTreeMap<(Principal, u64), DifficultyAdjustment, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DIFFICULTY_ADJUSTMENT_MEMORY_ID)),
+        )
+    );
+
+    // Learning path enrollments and progress, keyed by (user, learning path id)
+    pub static LEARNING_PATH_PROGRESS: RefCell<StableBTreeMap<(Principal, u64), LearningPathProgress, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LEARNING_PATH_PROGRESS_MEMORY_ID)),
+        )
+    );
+
+    // (user, learning path id, module id) -> completion recorded for that module
+    pub static PATH_MODULE_COMPLETIONS: RefCell<StableBTreeMap<(Principal, u64, u64), ModuleCompletion, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(PATH_MODULE_COMPLETION_MEMORY_ID)),
+        )
+    );
+
+    // Topic taxonomy shared by study groups, learning paths and tutors
+    pub static TOPICS: RefCell<StableBTreeMap<u64, Topic, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TOPIC_MEMORY_ID)),
+        )
+    );
+
+    // Topic tag indexes, keyed by (topic id, tagged entity id)
+    pub static TOPIC_GROUPS: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TOPIC_GROUP_INDEX_MEMORY_ID)),
+        )
+    );
+
+    pub static TOPIC_PATHS: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TOPIC_PATH_INDEX_MEMORY_ID)),
+        )
+    );
+
+    pub static TOPIC_TUTORS: RefCell<StableBTreeMap<(u64, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TOPIC_TUTOR_INDEX_MEMORY_ID)),
+        )
+    );
+
+    // Issued completion certificates
+    pub static CERTIFICATES: RefCell<StableBTreeMap<u64, Certificate, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CERTIFICATE_MEMORY_ID)),
+        )
+    );
+
+    // Merkle leaf hash of each certificate, in issuance order
+    pub static CERTIFICATE_LEAVES: RefCell<StableBTreeMap<u64, [u8; 32], Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CERTIFICATE_LEAF_MEMORY_ID)),
+        )
+    );
+
+    // Certificate id -> position of its leaf in the Merkle tree
+    pub static CERTIFICATE_POSITIONS: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CERTIFICATE_POSITION_MEMORY_ID)),
+        )
+    );
+
+    // (level, position) -> node hash of the certificate Merkle tree; level 0 holds the leaves
+    pub static CERTIFICATE_TREE: RefCell<StableBTreeMap<(u8, u64), [u8; 32], Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CERTIFICATE_TREE_MEMORY_ID)),
+        )
+    );
+
+    pub static CERTIFICATES_BY_USER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CERTIFICATES_BY_USER_MEMORY_ID)),
+        )
+    );
+
+    // Per-user secondary indexes. Keys are (owner, entity id) so that a user's
+    // rows can be read with a range scan instead of iterating the whole table.
+    pub static CONNECTIONS_BY_USER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(CONNECTIONS_BY_USER_MEMORY_ID)),
+        )
+    );
+
+    pub static TUTORS_BY_USER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TUTORS_BY_USER_MEMORY_ID)),
+        )
+    );
+
+    // (user, group id) -> membership id
+    pub static MEMBERSHIPS_BY_USER: RefCell<StableBTreeMap<(Principal, u64), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MEMBERSHIPS_BY_USER_MEMORY_ID)),
+        )
+    );
+
+    // (group id, user) -> membership id
+    pub static MEMBERSHIPS_BY_GROUP: RefCell<StableBTreeMap<(u64, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MEMBERSHIPS_BY_GROUP_MEMORY_ID)),
+        )
+    );
+
+    pub static TASK_COMPLETIONS_BY_USER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TASK_COMPLETIONS_BY_USER_MEMORY_ID)),
+        )
+    );
+
+    pub static TUTOR_SESSIONS_BY_USER: RefCell<StableBTreeMap<(Principal, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(TUTOR_SESSIONS_BY_USER_MEMORY_ID)),
+        )
+    );
+
     // Stable cell for ID counters
     pub static ID_COUNTERS: RefCell<StableCell<IdCounters, Memory>> = RefCell::new(
         StableCell::init(
@@ -261,6 +887,91 @@ pub fn next_id(entity: &str) -> u64 {
                 writer.set(current_counters).unwrap();
                 writer.get().user_task_completion
             }
+            "learning_metrics" => {
+                current_counters.learning_metrics += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().learning_metrics
+            }
+            "difficulty_adjustment" => {
+                current_counters.difficulty_adjustment += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().difficulty_adjustment
+            }
+            "learning_path_progress" => {
+                current_counters.learning_path_progress += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().learning_path_progress
+            }
+            "topic" => {
+                current_counters.topic += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().topic
+            }
+            "certificate" => {
+                current_counters.certificate += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().certificate
+            }
+            "conversation" => {
+                current_counters.conversation += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().conversation
+            }
+            "activity_event" => {
+                current_counters.activity_event += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().activity_event
+            }
+            "group_join_request" => {
+                current_counters.group_join_request += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().group_join_request
+            }
+            "study_resource" => {
+                current_counters.study_resource += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().study_resource
+            }
+            "poll" => {
+                current_counters.poll += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().poll
+            }
+            "poll_vote" => {
+                current_counters.poll_vote += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().poll_vote
+            }
+            "study_session" => {
+                current_counters.study_session += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().study_session
+            }
+            "session_participant" => {
+                current_counters.session_participant += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().session_participant
+            }
+            "session_series" => {
+                current_counters.session_series += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().session_series
+            }
+            "group_activity" => {
+                current_counters.group_activity += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().group_activity
+            }
+            "module_completion" => {
+                current_counters.module_completion += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().module_completion
+            }
+            "tutor_course" => {
+                current_counters.tutor_course += 1;
+                writer.set(current_counters).unwrap();
+                writer.get().tutor_course
+            }
             _ => panic!("Unknown entity type for ID generation"),
         }
     })